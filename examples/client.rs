@@ -32,19 +32,14 @@ async fn main() -> Result<()> {
         None,
     )?;
 
-    let resp = client
-        .run(Put, &args.id, &args.api_version, args.body, None)
-        .await?;
+    let query = [("api-version".to_string(), args.api_version)];
+    let resp = client.run(Put, &args.id, &query, args.body, None).await?;
     println!("PUT response: {}", String::from_utf8(resp.body.to_vec())?);
 
-    let resp = client
-        .run(Get, &args.id, &args.api_version, None, None)
-        .await?;
+    let resp = client.run(Get, &args.id, &query, None, None).await?;
     println!("GET response: {}", String::from_utf8(resp.body.to_vec())?);
 
-    let resp = client
-        .run(Delete, &args.id, &args.api_version, None, None)
-        .await?;
+    let resp = client.run(Delete, &args.id, &query, None, None).await?;
     println!(
         "DELETE response: {}",
         String::from_utf8(resp.body.to_vec())?
@@ -0,0 +1,180 @@
+use anyhow::{anyhow, bail, Result};
+use azure_core::http::{ClientMethodOptions, Context, Method};
+use clap::ArgMatches;
+use serde_json::Value;
+
+use crate::client::{Client, ClientRunOptions, Response};
+use crate::store::{Account, Store};
+
+const SUBSCRIPTIONS_API_VERSION: &str = "2022-12-01";
+
+/// Runs the `account` commands, which manage the subscription that the commands run against by default.
+pub async fn run(
+    client: &Client,
+    ctx: &Context<'_>,
+    matches: &ArgMatches,
+) -> Result<Option<Value>> {
+    let store = Store::open()?;
+    match matches.subcommand() {
+        Some(("list", _)) => {
+            let account = store.account()?;
+            let mut subscriptions = list_subscriptions(client, ctx).await?;
+            for subscription in subscriptions.iter_mut() {
+                let is_default = account.as_ref().is_some_and(|account| {
+                    Some(account.id.as_str()) == subscription_id(subscription)
+                });
+                if let Value::Object(map) = subscription {
+                    map.insert("isDefault".to_string(), is_default.into());
+                }
+            }
+            Ok(Some(subscriptions.into()))
+        }
+        Some(("set", matches)) => {
+            let subscription = matches
+                .get_one::<String>("subscription")
+                .expect("the subscription is required");
+            let subscriptions = list_subscriptions(client, ctx).await?;
+            store.set_account(&find_account(&subscriptions, subscription)?)?;
+            Ok(None)
+        }
+        Some(("show", _)) => {
+            let account = store.account()?.ok_or(anyhow!(
+                r#"no default subscription, set it via "account set --subscription <name-or-id>""#
+            ))?;
+            Ok(Some(serde_json::to_value(account)?))
+        }
+        _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
+    }
+}
+
+// list_subscriptions returns the subscriptions that the identity can access, across all the pages.
+async fn list_subscriptions(client: &Client, ctx: &Context<'_>) -> Result<Vec<Value>> {
+    let method_options = ClientMethodOptions {
+        context: ctx.clone(),
+    };
+    let mut response = client
+        .run(
+            Method::Get,
+            "/subscriptions",
+            &[(
+                "api-version".to_string(),
+                SUBSCRIPTIONS_API_VERSION.to_string(),
+            )],
+            None,
+            Some(ClientRunOptions {
+                method_options: method_options.clone(),
+                long_running: Some(false),
+                ..ClientRunOptions::default()
+            }),
+        )
+        .await?;
+    let mut subscriptions = vec![];
+    loop {
+        let mut page = page(response)?;
+        if let Some(Value::Array(items)) = page.get_mut("value").map(Value::take) {
+            subscriptions.extend(items);
+        }
+        let Some(next_link) = page
+            .get("nextLink")
+            .and_then(Value::as_str)
+            .filter(|link| !link.is_empty())
+        else {
+            return Ok(subscriptions);
+        };
+        response = client
+            .run_next_link(next_link, Some(method_options.clone()))
+            .await?;
+    }
+}
+
+// page returns the body of a page of the subscriptions, or the error of the response.
+fn page(response: Response) -> Result<Value> {
+    if !response.status_code.is_success() {
+        return Err(azure_core::Error::from(response).into());
+    }
+    Ok(serde_json::from_slice(&response.body)?)
+}
+
+fn subscription_id(subscription: &Value) -> Option<&str> {
+    subscription.get("subscriptionId").and_then(Value::as_str)
+}
+
+// find_account finds the subscription by its id, or otherwise by its display name, both are case insensitive.
+fn find_account(subscriptions: &[Value], name_or_id: &str) -> Result<Account> {
+    let by_id = subscriptions.iter().find(|subscription| {
+        subscription_id(subscription).is_some_and(|id| id.eq_ignore_ascii_case(name_or_id))
+    });
+    let subscription = match by_id {
+        Some(subscription) => subscription,
+        None => {
+            let by_name: Vec<&Value> = subscriptions
+                .iter()
+                .filter(|subscription| {
+                    subscription
+                        .get("displayName")
+                        .and_then(Value::as_str)
+                        .is_some_and(|name| name.eq_ignore_ascii_case(name_or_id))
+                })
+                .collect();
+            match by_name.as_slice() {
+                [subscription] => *subscription,
+                [] => bail!(r#"subscription "{name_or_id}" not found"#),
+                _ => bail!(
+                    r#"there are multiple subscriptions named "{name_or_id}", specify the subscription id instead"#
+                ),
+            }
+        }
+    };
+    let field = |name| {
+        subscription
+            .get(name)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+    Ok(Account {
+        id: field("subscriptionId"),
+        name: field("displayName"),
+        tenant_id: field("tenantId"),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn find_account_by_name_or_id() {
+        let subscriptions = vec![
+            json!({"subscriptionId": "00000000-0000-0000-0000-000000000001", "displayName": "Dev", "tenantId": "t1"}),
+            json!({"subscriptionId": "00000000-0000-0000-0000-000000000002", "displayName": "Prod", "tenantId": "t1"}),
+            json!({"subscriptionId": "00000000-0000-0000-0000-000000000003", "displayName": "prod", "tenantId": "t2"}),
+        ];
+        let account = |id: &str, name: &str, tenant_id: &str| Account {
+            id: id.to_string(),
+            name: name.to_string(),
+            tenant_id: tenant_id.to_string(),
+        };
+
+        assert_eq!(
+            find_account(&subscriptions, "dev").unwrap(),
+            account("00000000-0000-0000-0000-000000000001", "Dev", "t1")
+        );
+        assert_eq!(
+            find_account(&subscriptions, "00000000-0000-0000-0000-000000000003").unwrap(),
+            account("00000000-0000-0000-0000-000000000003", "prod", "t2")
+        );
+        assert_eq!(
+            find_account(&subscriptions, "Prod")
+                .unwrap_err()
+                .to_string(),
+            r#"there are multiple subscriptions named "Prod", specify the subscription id instead"#
+        );
+        assert_eq!(
+            find_account(&subscriptions, "foo").unwrap_err().to_string(),
+            r#"subscription "foo" not found"#
+        );
+    }
+}
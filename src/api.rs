@@ -2,10 +2,16 @@ use crate::arg::CliInput;
 use anyhow::{anyhow, Result};
 use clap::ArgMatches;
 use invoke::CommandInvocation;
+use metadata::Command;
 use std::path::PathBuf;
 
+pub mod input;
 pub mod invoke;
 pub mod metadata;
+pub mod shorthand;
+pub mod types;
+mod update;
+mod validation;
 
 #[derive(Debug, Clone)]
 pub struct ApiManager {
@@ -20,14 +26,14 @@ impl ApiManager {
         raw_input: &CliInput,
         matches: &ArgMatches,
     ) -> Result<CommandInvocation> {
+        let c = self.resolve_command(raw_input)?;
+        Ok(CommandInvocation::new(&c, matches))
+    }
+
+    pub fn resolve_command(&self, raw_input: &CliInput) -> Result<Command> {
         let pos_args = raw_input.pos_args();
-        pos_args
-            .first()
-            .ok_or(anyhow!("the rp is not specified"))
-            .and_then(|rp| {
-                let c = self.read_metadata(rp)?.resolve_command(&raw_input)?;
-                Ok(CommandInvocation::new(&c, matches))
-            })
+        let rp = pos_args.first().ok_or(anyhow!("the rp is not specified"))?;
+        self.read_metadata(rp)?.resolve_command(raw_input)
     }
 }
 
@@ -84,12 +90,11 @@ mod fs {
                 .context(format!("reading dir {}", path.display()))?
             {
                 let path = entry?.path();
-                if let Some(ext) = path.extension() {
-                    if ext == "json" {
-                        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                            rps.push(stem.to_owned());
-                        }
-                    }
+                if let Some(ext) = path.extension()
+                    && ext == "json"
+                    && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+                {
+                    rps.push(stem.to_owned());
                 }
             }
             Ok(Self { path, rps })
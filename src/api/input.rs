@@ -0,0 +1,89 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// Loads the argument value from the file (`@path`) or stdin (`@-`), otherwise the raw value is returned as is.
+///
+/// The YAML content, i.e. a `.yaml`/`.yml` file or the stdin that is not JSON, is converted to JSON.
+pub fn load(raw: &str) -> Result<String> {
+    let Some(path) = raw.strip_prefix('@') else {
+        return Ok(raw.to_string());
+    };
+    if path == "-" {
+        let content = read_stdin()?;
+        if serde_json::from_str::<Value>(&content).is_ok() {
+            return Ok(content);
+        }
+        // The plain text is kept as is, which is also a valid YAML string
+        return match serde_yaml::from_str::<Value>(&content) {
+            Ok(value @ (Value::Object(_) | Value::Array(_))) => Ok(value.to_string()),
+            _ => Ok(content),
+        };
+    }
+    let content = read_file(path)?;
+    if path.ends_with(".yaml") || path.ends_with(".yml") {
+        let value: Value =
+            serde_yaml::from_str(&content).map_err(|e| anyhow!("parsing YAML {path}: {e}"))?;
+        return Ok(value.to_string());
+    }
+    Ok(content)
+}
+
+// read_file reads the file, whose error includes the cause, as clap reports the error without its chain.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_file(path: &str) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| anyhow!("reading {path}: {e}"))
+}
+
+// read_stdin reads the stdin once, whose content is kept for the later reads, as the arguments are parsed again for
+// each resource id of "--ids".
+#[cfg(not(target_arch = "wasm32"))]
+fn read_stdin() -> Result<String> {
+    use std::io::Read;
+    use std::sync::OnceLock;
+    static STDIN: OnceLock<String> = OnceLock::new();
+    if let Some(content) = STDIN.get() {
+        return Ok(content.clone());
+    }
+    let mut content = String::new();
+    std::io::stdin()
+        .read_to_string(&mut content)
+        .map_err(|e| anyhow!("reading stdin: {e}"))?;
+    Ok(STDIN.get_or_init(|| content).clone())
+}
+
+// There is no file system or stdin in the browser.
+#[cfg(target_arch = "wasm32")]
+fn read_file(path: &str) -> Result<String> {
+    anyhow::bail!("reading {path} is not supported in the browser")
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_stdin() -> Result<String> {
+    anyhow::bail!("reading stdin is not supported in the browser")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn load_files() {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&dir).unwrap();
+        let yaml = dir.join("body.yaml");
+        std::fs::write(&yaml, "location: westus\ntags:\n  env: dev\n").unwrap();
+        let text = dir.join("script.sh");
+        std::fs::write(&text, "echo foo\n").unwrap();
+
+        assert_eq!(load("foo").unwrap(), "foo");
+        assert_eq!(
+            load(&format!("@{}", yaml.display())).unwrap(),
+            r#"{"location":"westus","tags":{"env":"dev"}}"#
+        );
+        assert_eq!(load(&format!("@{}", text.display())).unwrap(), "echo foo\n");
+        assert!(load(&format!("@{}", dir.join("missing.json").display())).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
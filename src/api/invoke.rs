@@ -1,15 +1,108 @@
-use core::unreachable;
 use std::collections::HashMap;
 
-use crate::client::Client;
+use crate::client::{
+    interrupted, Client, ClientRunOptions, FinalStateVia, NewPollerOptions, Poller, Response,
+};
+use crate::error::{ArmError, Interrupted, InvalidInput};
+use crate::store::{Job, Store};
 
-use super::metadata::{Command, Operation, Schema};
-use anyhow::{bail, Result};
+use super::metadata::{self, Command, ConditionOperator, InstanceUpdate, Operation, Schema};
+use super::shorthand;
+use super::types::ArgType;
+use super::update::GenericUpdate;
+use super::validation;
+use anyhow::{anyhow, bail, Result};
+use azure_core::base64;
+use azure_core::error::ErrorKind;
+use azure_core::http::{ClientMethodOptions, Context};
 use clap::ArgMatches;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// PATH_SEGMENT is the characters to encode in a path segment, including "/" which would split the segment.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+// Variables holds the values bound during the invocation, keyed by the variable name (e.g. "$Instance").
+#[derive(Debug, Default)]
+struct Variables(HashMap<String, Value>);
+
+impl Variables {
+    fn insert(&mut self, var: &str, value: Value) {
+        self.0.insert(var.to_string(), value);
+    }
+
+    // get returns the value referenced by the reference, which is a variable name optionally followed by a
+    // property path, e.g. "$Instance.properties".
+    fn get(&self, ref_: &str) -> Option<&Value> {
+        let mut segments = ref_.split('.');
+        let mut value = self.0.get(segments.next()?)?;
+        for segment in segments {
+            value = value.get(segment)?;
+        }
+        Some(value)
+    }
+
+    fn get_mut(&mut self, ref_: &str) -> Option<&mut Value> {
+        let mut segments = ref_.split('.');
+        let mut value = self.0.get_mut(segments.next()?)?;
+        for segment in segments {
+            value = value.get_mut(segment)?;
+        }
+        Some(value)
+    }
+}
+
+const DEFAULT_NEXT_LINK_NAME: &str = "nextLink";
+const DEFAULT_ITEM_NAME: &str = "value";
+
+// PageToken records where a truncated pagination stops, so that it can be resumed by a later command.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct PageToken {
+    // The link of the page where the pagination stops. None means the first page.
+    #[serde(rename = "nextLink")]
+    next_link: Option<String>,
+    // The offset of the first remaining item in that page.
+    offset: usize,
+}
+
+impl PageToken {
+    fn encode(&self) -> Result<String> {
+        Ok(base64::encode(serde_json::to_vec(self)?))
+    }
+
+    fn decode(token: &str) -> Result<Self> {
+        let invalid = || InvalidInput {
+            message: format!("invalid next token: {token}"),
+        };
+        let bytes = base64::decode(token).map_err(|_| invalid())?;
+        Ok(serde_json::from_slice(&bytes).map_err(|_| invalid())?)
+    }
+}
+
+// Outstanding is a LRO that is left running when the operation returns.
+pub struct Outstanding {
+    poller: Poller,
+    // The interruption of the command, None means it is started with "--no-wait".
+    interrupted: Option<Interrupted>,
+}
 
 pub struct CommandInvocation {
     command: Command,
     matches: ArgMatches,
+    // The command line, which is recorded for the operations started with "--no-wait".
+    command_line: String,
 }
 
 impl CommandInvocation {
@@ -17,16 +110,343 @@ impl CommandInvocation {
         Self {
             command: command.clone(),
             matches: matches.clone(),
+            command_line: command.name.clone(),
         }
     }
 
-    pub async fn invoke(&self, client: &Client) -> Result<String> {
+    pub fn with_command_line(mut self, command_line: String) -> Self {
+        self.command_line = command_line;
+        self
+    }
+
+    // invoke runs the operations of the command in order, skipping those whose conditions don't hold.
+    // The response bodies are bound to the declared variables (e.g. "$Instance"), which can be referenced by
+    // the request bodies of the later operations, as well as the outputs.
+    pub async fn invoke(&self, client: &Client, ctx: &Context<'_>) -> Result<Option<Value>> {
         if self.command.operations.is_empty() {
             bail!("No operation found for command {}", self.command.name);
         }
-        let operation = self.command.operations.first().unwrap();
-        let operation_ionvocation = OperationInvocation::new(operation, &self.matches);
-        operation_ionvocation.invoke(client).await
+        validation::validate(&self.command, &self.matches)?;
+        let classes = self.command.schema_classes();
+        let update = GenericUpdate::from_matches(&self.matches).map_err(InvalidInput::from)?;
+        // Validate the edits before sending any request, so that typos don't end up with a partial update
+        if let Some(schema) = self
+            .command
+            .instance_update()
+            .and_then(|iu| iu.json.schema.as_ref())
+        {
+            update
+                .validate(schema, &classes)
+                .map_err(InvalidInput::from)?;
+        }
+
+        let mut vars = Variables::default();
+        for condition in self.command.conditions.iter().flatten() {
+            let value = self.evaluate(&condition.operator);
+            vars.insert(&condition.var, Value::Bool(value));
+        }
+        for operation in &self.command.operations {
+            if !self.should_run(operation, &vars)? {
+                continue;
+            }
+            match &operation.instance_update {
+                Some(instance_update) => {
+                    self.update_instance(operation, instance_update, &update, &classes, &mut vars)?
+                }
+                None => {
+                    let outstanding = OperationInvocation::new(operation, &self.matches)
+                        .invoke(client, ctx, &mut vars)
+                        .await?;
+                    // The LRO is left running, record it so that it can be waited later
+                    if let Some(Outstanding {
+                        poller,
+                        interrupted,
+                    }) = outstanding
+                    {
+                        let job = self.save_job(&poller)?;
+                        if let Some(interrupted) = interrupted {
+                            return Err(Interrupted {
+                                id: Some(job.id),
+                                ..interrupted
+                            }
+                            .into());
+                        }
+                        return Ok(Some(serde_json::to_value(job)?));
+                    }
+                }
+            }
+        }
+        Ok(self.build_outputs(&vars))
+    }
+
+    fn no_wait(&self) -> bool {
+        self.matches.try_get_one::<bool>("no-wait").ok().flatten() == Some(&true)
+    }
+
+    fn save_job(&self, poller: &Poller) -> Result<Job> {
+        let job = Job {
+            id: uuid::Uuid::new_v4().to_string(),
+            command: self.command_line.clone(),
+            status: poller.status(),
+            created_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            handle: poller.handle(),
+        };
+        Store::open()?.put_job(job.clone())?;
+        Ok(job)
+    }
+
+    // evaluate evaluates the condition operator against the arguments.
+    fn evaluate(&self, operator: &ConditionOperator) -> bool {
+        match operator {
+            ConditionOperator::And { operators } => operators.iter().all(|op| self.evaluate(op)),
+            ConditionOperator::Or { operators } => operators.iter().any(|op| self.evaluate(op)),
+            ConditionOperator::Not { operator } => !self.evaluate(operator),
+            ConditionOperator::HasValue { arg } => {
+                self.matches.try_get_raw(arg).ok().flatten().is_some()
+            }
+        }
+    }
+
+    // should_run tells whether the operation shall run, i.e. all the conditions it depends on hold.
+    fn should_run(&self, operation: &Operation, vars: &Variables) -> Result<bool> {
+        for var in operation.when.iter().flatten() {
+            match vars.get(var) {
+                Some(Value::Bool(value)) => {
+                    if !value {
+                        return Ok(false);
+                    }
+                }
+                _ => bail!(r#"condition "{var}" is not defined"#),
+            }
+        }
+        Ok(true)
+    }
+
+    // update_instance applies the properties specified by the arguments, followed by the generic update edits,
+    // to the referenced instance.
+    fn update_instance(
+        &self,
+        operation: &Operation,
+        instance_update: &InstanceUpdate,
+        update: &GenericUpdate,
+        classes: &HashMap<String, Schema>,
+        vars: &mut Variables,
+    ) -> Result<()> {
+        let instance = vars
+            .get_mut(&instance_update.ref_)
+            .ok_or(anyhow!(r#"instance "{}" not found"#, instance_update.ref_))?;
+        let Some(schema) = &instance_update.json.schema else {
+            return Ok(());
+        };
+        if let Some(patch) = OperationInvocation::new(operation, &self.matches)
+            .build_body(schema.clone(), classes)?
+        {
+            merge_value(instance, patch, schema, classes);
+        }
+        // The edits are validated against the schema, but the paths can still be absent in the instance
+        Ok(update
+            .apply(instance, schema, classes)
+            .map_err(InvalidInput::from)?)
+    }
+
+    // build_outputs builds the command result from the outputs declared by the command.
+    // A command without outputs results in nothing, while multiple outputs result in an array.
+    fn build_outputs(&self, vars: &Variables) -> Option<Value> {
+        let classes = self.command.schema_classes();
+        let mut outputs: Vec<Value> = self
+            .command
+            .outputs
+            .iter()
+            .flatten()
+            .filter_map(|output| {
+                let value = vars.get(&output.ref_)?;
+                match self.ref_schema(&output.ref_, &classes) {
+                    Some(schema) if output.client_flatten == Some(true) => {
+                        Some(flatten_value(value, &schema, &classes))
+                    }
+                    _ => Some(value.clone()),
+                }
+            })
+            .collect();
+        match outputs.len() {
+            0 => None,
+            1 => outputs.pop(),
+            _ => Some(Value::Array(outputs)),
+        }
+    }
+
+    // ref_schema returns the schema of the value referenced by the reference, e.g. "$Instance.value".
+    fn ref_schema(&self, ref_: &str, classes: &HashMap<String, Schema>) -> Option<Schema> {
+        let mut segments = ref_.split('.');
+        let mut schema = self.command.var_schema(segments.next()?)?.resolve(classes);
+        for segment in segments {
+            schema = schema.prop(segment)?.resolve(classes);
+        }
+        Some(schema)
+    }
+
+    /// Returns the confirmation message of the command, if it needs to be confirmed before running.
+    pub fn confirmation(&self) -> Option<&str> {
+        self.command
+            .confirmation
+            .as_deref()
+            .filter(|msg| !msg.is_empty())
+    }
+
+    /// Returns the schema of the command result, if declared in the metadata.
+    pub fn output_schema(&self) -> Option<Schema> {
+        if self.no_wait() {
+            return None;
+        }
+        let classes = self.command.schema_classes();
+        let output = self.command.outputs.as_ref()?.first()?;
+        let schema = self.ref_schema(&output.ref_, &classes)?;
+        if output.client_flatten == Some(true) {
+            Some(flatten_schema(&schema, &classes))
+        } else {
+            Some(schema)
+        }
+    }
+}
+
+// merge_value merges the patch built from the arguments into the instance.
+// The properties that are set by an argument replace the original ones, while the others are merged recursively.
+fn merge_value(
+    instance: &mut Value,
+    patch: Value,
+    schema: &Schema,
+    classes: &HashMap<String, Schema>,
+) {
+    let (Value::Object(target), Value::Object(patch)) = (&mut *instance, &patch) else {
+        *instance = patch;
+        return;
+    };
+    for (k, v) in patch {
+        match (
+            schema.prop(k).map(|p| p.resolve(classes)),
+            target.get_mut(k),
+        ) {
+            (Some(prop), Some(origin)) if prop.arg.is_none() => {
+                merge_value(origin, v.clone(), &prop, classes)
+            }
+            _ => {
+                target.insert(k.clone(), v.clone());
+            }
+        }
+    }
+}
+
+// method_options returns the options to send the request within the context of the command.
+fn method_options<'a>(ctx: &Context<'a>) -> ClientMethodOptions<'a> {
+    ClientMethodOptions {
+        context: ctx.clone(),
+    }
+}
+
+// flatten_value lifts the properties of the client flattened properties (per schema) to their parent object.
+fn flatten_value(value: &Value, schema: &Schema, classes: &HashMap<String, Schema>) -> Value {
+    match value {
+        Value::Object(map) if schema.props.is_some() => {
+            let mut out = serde_json::Map::new();
+            for (k, v) in map {
+                match schema.prop(k).map(|p| p.resolve(classes)) {
+                    Some(prop) if prop.client_flatten == Some(true) => {
+                        if let Value::Object(inner) = flatten_value(v, &prop, classes) {
+                            out.extend(inner);
+                        } else {
+                            out.insert(k.clone(), v.clone());
+                        }
+                    }
+                    Some(prop) => {
+                        out.insert(k.clone(), flatten_value(v, &prop, classes));
+                    }
+                    None => {
+                        out.insert(k.clone(), v.clone());
+                    }
+                }
+            }
+            Value::Object(out)
+        }
+        Value::Array(arr) => match &schema.item {
+            Some(item) => {
+                let item = item.resolve(classes);
+                Value::Array(
+                    arr.iter()
+                        .map(|v| flatten_value(v, &item, classes))
+                        .collect(),
+                )
+            }
+            None => value.clone(),
+        },
+        _ => value.clone(),
+    }
+}
+
+// flatten_schema is the schema counterpart of flatten_value.
+fn flatten_schema(schema: &Schema, classes: &HashMap<String, Schema>) -> Schema {
+    flatten_schema_in(schema, classes, &mut vec![])
+}
+
+// flatten_schema_in flattens the schema within the classes being flattened, which are not resolved again, as the
+// classes can be recursive (e.g. the subnets of a virtual network refer to the virtual network).
+fn flatten_schema_in(
+    schema: &Schema,
+    classes: &HashMap<String, Schema>,
+    within: &mut Vec<String>,
+) -> Schema {
+    let cls = match schema.type_.strip_prefix('@') {
+        Some(cls) if within.iter().any(|c| c == cls) => return schema.clone(),
+        Some(cls) => Some(cls.to_string()),
+        None => schema.cls.clone(),
+    };
+    let schema = schema.resolve(classes);
+    within.extend(cls.clone());
+
+    let mut out = schema.clone();
+    if let Some(props) = &schema.props {
+        let mut flattened = vec![];
+        for prop in props.iter().map(|p| flatten_schema_in(p, classes, within)) {
+            if prop.client_flatten == Some(true) && prop.props.is_some() {
+                flattened.extend(prop.props.unwrap_or_default());
+            } else {
+                flattened.push(prop);
+            }
+        }
+        out.props = Some(flattened);
+    }
+    if let Some(item) = &schema.item {
+        out.item = Some(Box::new(flatten_schema_in(item, classes, within)));
+    }
+
+    if cls.is_some() {
+        within.pop();
+    }
+    out
+}
+
+// missing_parameter returns the error of a required request parameter whose argument is not specified.
+fn missing_parameter(kind: &str, name: &str) -> anyhow::Error {
+    InvalidInput {
+        message: format!("missing required {kind} parameter: {name}"),
+    }
+    .into()
+}
+
+// error_response returns the error of the response that is not declared as a success by the metadata.
+// The ARM errors (declared as "@MgmtErrorFormat") are parsed, otherwise the raw body is reported along with the
+// status, which classifies the error.
+fn error_response(response: Response) -> anyhow::Error {
+    match ArmError::from_body(u16::from(response.status_code), &response.body) {
+        Some(e) => e.into(),
+        None => azure_core::Error::message(
+            ErrorKind::http_response(response.status_code, None),
+            format!(
+                "error response: {}\n\n{}",
+                response.status_code,
+                String::from_utf8_lossy(&response.body)
+            ),
+        )
+        .into(),
     }
 }
 
@@ -43,7 +463,14 @@ impl OperationInvocation {
         }
     }
 
-    pub async fn invoke(&self, client: &crate::client::Client) -> Result<String> {
+    // invoke runs the operation and binds the response body to the declared variable.
+    // It returns the LRO that is left running instead, with "--no-wait" or once the command is interrupted.
+    pub async fn invoke(
+        &self,
+        client: &crate::client::Client,
+        ctx: &Context<'_>,
+        vars: &mut Variables,
+    ) -> Result<Option<Outstanding>> {
         if self.operation.http.is_none() {
             bail!(
                 r#"HTTP information not found for operation "{}""#,
@@ -55,63 +482,333 @@ impl OperationInvocation {
         }
 
         let http = self.operation.http.as_ref().unwrap();
+        let path = self.build_path(http)?;
+        let query_pairs = self.query_pairs(&http.request.query)?;
+        let headers = match &http.request.header {
+            Some(header) => self.headers(header)?,
+            None => vec![],
+        };
+        let body: Option<bytes::Bytes> = match http.request.body.as_ref().map(|b| &b.json) {
+            // The body refers to a variable bound by a previous operation, e.g. the updated instance
+            Some(metadata::BodyJSON {
+                ref_: Some(ref_), ..
+            }) => {
+                let value = vars
+                    .get(ref_)
+                    .ok_or(anyhow!(r#"request body "{ref_}" not found"#))?;
+                Some(bytes::Bytes::from(value.to_string()))
+            }
+            Some(metadata::BodyJSON {
+                schema: Some(schema),
+                ..
+            }) => self
+                .build_body_with_override(schema, &self.operation.schema_classes())?
+                .map(|v| bytes::Bytes::from(v.to_string())),
+            _ => None,
+        };
+        let token = self.next_token()?;
+        let response = match token.as_ref().and_then(|t| t.next_link.as_deref()) {
+            // Resume the pagination from the page where the previous command stopped
+            Some(next_link) => {
+                client
+                    .run_next_link(next_link, Some(method_options(ctx)))
+                    .await?
+            }
+            None => {
+                let options = Some(ClientRunOptions {
+                    method_options: method_options(ctx),
+                    headers,
+                    // The LRO is detected from the response, unless the metadata declares it
+                    long_running: self.operation.long_running.as_ref().map(|_| true),
+                    poller_options: self.poller_options()?,
+                });
+                let method = http.request.method.into();
+                let (response, poller) = client
+                    .begin(method, path.as_str(), &query_pairs, body, options)
+                    .await?;
+                match poller {
+                    Some(poller) if self.no_wait() && !poller.done() => {
+                        return Ok(Some(Outstanding {
+                            poller,
+                            interrupted: None,
+                        }));
+                    }
+                    Some(mut poller) => match client.wait(ctx, &mut poller).await {
+                        Ok(response) => response,
+                        Err(e) => match interrupted(&e) {
+                            Some(interrupted) if !poller.done() => {
+                                let interrupted = Interrupted {
+                                    status: Some(poller.status()),
+                                    ..interrupted.clone()
+                                };
+                                return Ok(Some(Outstanding {
+                                    poller,
+                                    interrupted: Some(interrupted),
+                                }));
+                            }
+                            _ => return Err(e.into()),
+                        },
+                    },
+                    None => response,
+                }
+            }
+        };
+        let Some(response_meta) = self.response_meta(&response) else {
+            return Err(error_response(response));
+        };
+
+        // Bind the response body to the declared variable
+        if let Some(var) = response_meta
+            .body
+            .as_ref()
+            .and_then(|body| body.json.var.as_ref())
+            && !response.body.is_empty()
+        {
+            let mut value: Value = serde_json::from_slice(&response.body)?;
+            if let Some((next_link_name, item_name)) = self.paging(&value) {
+                value = self
+                    .collect_pages(client, ctx, value, &next_link_name, &item_name, token)
+                    .await?;
+            }
+            vars.insert(var, value);
+        }
+        Ok(None)
+    }
+
+    // poller_options returns the options of the LRO poller declared by the metadata.
+    fn poller_options(&self) -> Result<Option<NewPollerOptions>> {
+        let Some(long_running) = &self.operation.long_running else {
+            return Ok(None);
+        };
+        Ok(Some(NewPollerOptions {
+            final_state: long_running
+                .final_state_via
+                .as_deref()
+                .map(str::parse::<FinalStateVia>)
+                .transpose()?,
+            operation_location_result_path: long_running.result_path.clone(),
+        }))
+    }
+
+    fn no_wait(&self) -> bool {
+        self.matches.try_get_one::<bool>("no-wait").ok().flatten() == Some(&true)
+    }
+
+    // build_path fills the path parameters in the path template.
+    // The segment of an optional path parameter that is not specified is removed from the path.
+    fn build_path(&self, http: &metadata::Http) -> Result<String> {
         let mut path = http.path.clone();
         for param in &http.request.path.params {
-            if let Some(value) = self.matches.get_one::<String>(&param.arg) {
-                path = path.replace(&format!("{{{}}}", param.name), value);
+            let placeholder = format!("{{{}}}", param.name);
+            if let Some(value) = self.arg_string(&param.arg) {
+                let value = utf8_percent_encode(&value, PATH_SEGMENT).to_string();
+                path = path.replace(&placeholder, &value);
             } else if let Some(true) = param.required {
-                bail!("missing required path parameter: {}", param.name);
+                return Err(missing_parameter("path", &param.name));
             } else {
-                unreachable!(
-                    r#"optional path parameter "{}" not supported yet!"#,
-                    param.name
-                )
+                path = path
+                    .replace(&format!("/{placeholder}"), "")
+                    .replace(&placeholder, "");
             }
         }
-        let mut query_pairs = HashMap::new();
-        // TODO: handle query parameters (query.params)
-        for param in &http.request.query.consts {
-            query_pairs.insert(param.name.clone(), param.default.value.clone());
+        Ok(path)
+    }
+
+    // headers returns the request headers bound to the arguments, followed by the constant ones.
+    fn headers(&self, header: &metadata::RequestHeader) -> Result<Vec<(String, String)>> {
+        let mut headers = vec![];
+        for param in header.params.iter().flatten() {
+            if let Some(value) = self.arg_string(&param.arg) {
+                headers.push((param.name.clone(), value));
+            } else if let Some(true) = param.required {
+                return Err(missing_parameter("header", &param.name));
+            }
         }
-        let body: Option<bytes::Bytes> = if let Some(body_meta) = &http.request.body {
-            if let Some(schema) = &body_meta.json.schema {
-                self.build_body(schema.clone())?
-                    .map(|v| bytes::Bytes::from(v.to_string()))
-            } else {
-                None
+        for param in header.consts.iter().flatten() {
+            headers.push((param.name.clone(), param.default.value.clone()));
+        }
+        Ok(headers)
+    }
+
+    // query_pairs returns the query parameters bound to the arguments, followed by the constant ones (e.g. "api-version").
+    fn query_pairs(&self, query: &metadata::RequestQuery) -> Result<Vec<(String, String)>> {
+        let mut pairs = vec![];
+        for param in query.params.iter().flatten() {
+            if let Some(value) = self.arg_string(&param.arg) {
+                pairs.push((param.name.clone(), value));
+            } else if let Some(true) = param.required {
+                return Err(missing_parameter("query", &param.name));
             }
-        } else {
-            None
+        }
+        for param in &query.consts {
+            pairs.push((param.name.clone(), param.default.value.clone()));
+        }
+        Ok(pairs)
+    }
+
+    // arg_string returns the value of the argument as a string, which is used to build the path and query.
+    fn arg_string(&self, arg: &str) -> Option<String> {
+        self.matches
+            .try_get_one::<String>(arg)
+            .ok()
+            .flatten()
+            .cloned()
+    }
+
+    // response_meta returns the declared successful response that matches the status code of the response.
+    fn response_meta(&self, response: &Response) -> Option<&metadata::Response> {
+        let http = self.operation.http.as_ref()?;
+        http.responses.iter().find(|response_meta| {
+            response_meta
+                .status_code
+                .as_ref()
+                .is_some_and(|codes| codes.contains(&(u16::from(response.status_code) as i64)))
+        })
+    }
+
+    // paging returns the names of the next link and the items of a paged response body.
+    // The pageable metadata is preferred, otherwise it falls back to detect the conventional "nextLink" and "value".
+    fn paging(&self, body: &Value) -> Option<(String, String)> {
+        if let Some(pageable) = &self.operation.pageable {
+            return Some((
+                pageable
+                    .next_link_name
+                    .clone()
+                    .unwrap_or(DEFAULT_NEXT_LINK_NAME.to_string()),
+                pageable
+                    .item_name
+                    .clone()
+                    .unwrap_or(DEFAULT_ITEM_NAME.to_string()),
+            ));
+        }
+        if body.get(DEFAULT_NEXT_LINK_NAME).is_some()
+            && body.get(DEFAULT_ITEM_NAME).is_some_and(Value::is_array)
+        {
+            return Some((
+                DEFAULT_NEXT_LINK_NAME.to_string(),
+                DEFAULT_ITEM_NAME.to_string(),
+            ));
+        }
+        None
+    }
+
+    fn next_token(&self) -> Result<Option<PageToken>> {
+        match self
+            .matches
+            .try_get_one::<String>("next-token")
+            .ok()
+            .flatten()
+        {
+            Some(token) => Ok(Some(PageToken::decode(token)?)),
+            None => Ok(None),
+        }
+    }
+
+    // collect_pages follows the next links of the first page, and merges the items of all the pages into it.
+    // When "--max-items" is specified, it stops once the limit is reached, and reports a token to resume from there.
+    async fn collect_pages(
+        &self,
+        client: &Client,
+        ctx: &Context<'_>,
+        first_page: Value,
+        next_link_name: &str,
+        item_name: &str,
+        token: Option<PageToken>,
+    ) -> Result<Value> {
+        let max_items = self
+            .matches
+            .try_get_one::<usize>("max-items")
+            .ok()
+            .flatten()
+            .copied();
+        let (mut page_link, mut offset) = match token {
+            Some(token) => (token.next_link, token.offset),
+            None => (None, 0),
         };
-        let response = client
-            .run(
-                http.request.method.into(),
-                path.as_str(),
-                &query_pairs["api-version"],
-                body,
-                None,
-            )
-            .await?;
-        for response_meta in &http.responses {
-            if let Some(status_codes) = &response_meta.status_code {
-                if status_codes.contains(&(u16::from(response.status_code) as i64)) {
-                    return Ok(String::from_utf8(response.body.to_vec())?);
+        let mut result = first_page.clone();
+        let mut page = first_page;
+        let mut items = vec![];
+        let mut next_token = None;
+        'pages: loop {
+            let page_items = match page.get_mut(item_name).map(Value::take) {
+                Some(Value::Array(arr)) => arr,
+                _ => vec![],
+            };
+            for (i, item) in page_items.into_iter().enumerate().skip(offset) {
+                if max_items.is_some_and(|max| items.len() >= max) {
+                    next_token = Some(PageToken {
+                        next_link: page_link,
+                        offset: i,
+                    });
+                    break 'pages;
                 }
+                items.push(item);
+            }
+            offset = 0;
+
+            let Some(next_link) = page
+                .get(next_link_name)
+                .and_then(Value::as_str)
+                .filter(|link| !link.is_empty())
+                .map(str::to_string)
+            else {
+                break;
+            };
+            if max_items.is_some_and(|max| items.len() >= max) {
+                next_token = Some(PageToken {
+                    next_link: Some(next_link),
+                    offset: 0,
+                });
+                break;
+            }
+            let response = client
+                .run_next_link(&next_link, Some(method_options(ctx)))
+                .await?;
+            if self.response_meta(&response).is_none() {
+                return Err(error_response(response));
             }
+            page = serde_json::from_slice(&response.body)?;
+            page_link = Some(next_link);
         }
-        bail!(
-            "error response: {}\n\n{}",
-            response.status_code,
-            String::from_utf8_lossy(&response.body)
-        );
+
+        if let Some(token) = next_token {
+            crate::warn(&format!("Next token: {}", token.encode()?));
+        }
+        if let Value::Object(map) = &mut result {
+            map.insert(item_name.to_string(), Value::Array(items));
+            map.remove(next_link_name);
+        }
+        Ok(result)
+    }
+
+    // build_body_with_override builds the request body from the arguments, which are merged into the body specified
+    // by "--body", if any.
+    fn build_body_with_override(
+        &self,
+        schema: &Schema,
+        classes: &HashMap<String, Schema>,
+    ) -> Result<Option<Value>> {
+        let body = self.build_body(schema.clone(), classes)?;
+        let Some(raw) = self.arg_string("body") else {
+            return Ok(body);
+        };
+        let mut base = shorthand::parse(&raw).map_err(InvalidInput::from)?;
+        if let Some(body) = body {
+            merge_value(&mut base, body, schema, classes);
+        }
+        Ok(Some(base))
     }
 
-    fn build_body(&self, schema: Schema) -> Result<Option<serde_json::Value>> {
+    fn build_body(
+        &self,
+        schema: Schema,
+        classes: &HashMap<String, Schema>,
+    ) -> Result<Option<serde_json::Value>> {
         if let Some(props) = &schema.props {
             let mut map = serde_json::Map::new();
             for prop in props {
                 if let Some(prop_name) = &prop.name {
-                    let value = self.build_value(prop.clone())?;
+                    let value = self.build_value(prop.clone(), classes)?;
                     if let Some(value) = value {
                         map.insert(prop_name.clone(), value);
                     }
@@ -124,98 +821,419 @@ impl OperationInvocation {
         bail!(r#"schema lacks the "props" in the schema"#);
     }
 
-    fn build_value(&self, schema: Schema) -> Result<Option<serde_json::Value>> {
-        match schema.type_.as_str() {
-            "object" => {
-                if let Some(arg) = &schema.arg {
-                    if let Some(value) = self.matches.get_one::<String>(arg) {
-                        Ok(Some(serde_json::from_str(value)?))
-                    } else {
-                        Ok(None)
-                    }
-                } else if let Some(props) = &schema.props {
-                    let mut map = serde_json::Map::new();
-                    for prop in props {
-                        if let Some(prop_name) = &prop.name {
-                            let value = self.build_value(prop.clone())?;
-                            if let Some(value) = value {
-                                map.insert(prop_name.clone(), value);
-                            }
-                        } else {
-                            bail!(r#"property lacks the "name" in the schema"#,);
-                        }
-                    }
-                    if map.is_empty() {
-                        Ok(None)
-                    } else {
-                        Ok(Some(serde_json::Value::Object(map)))
-                    }
-                } else {
-                    bail!(r#"object schema lacks both the "arg" and "props" in the schema"#);
-                }
-            }
-            s if s.starts_with("array") || s == "string" => {
-                if let Some(arg) = &schema.arg {
-                    if let Some(value) = self.matches.get_one::<String>(arg) {
-                        Ok(Some(serde_json::from_str(value)?))
-                    } else {
-                        Ok(None)
-                    }
-                } else {
-                    bail!(
-                        r#"schema "{}" lacks the "arg" in the schema"#,
-                        schema.name.unwrap_or("".to_string())
-                    );
-                }
-            }
-            s if s.starts_with("integer") => {
-                if let Some(arg) = &schema.arg {
-                    if let Some(value) = self.matches.get_one::<i32>(arg) {
-                        Ok(Some((*value).into()))
-                    } else {
-                        Ok(None)
-                    }
-                } else {
-                    bail!(
-                        r#"schema "{}" lacks the "arg" in the schema"#,
-                        schema.name.unwrap_or("".to_string())
-                    );
+    // build_value builds the value of the schema from the argument bound to it, which is converted by the type of
+    // the schema (including the objects and arrays in the shorthand syntax), or from its properties recursively.
+    fn build_value(
+        &self,
+        schema: Schema,
+        classes: &HashMap<String, Schema>,
+    ) -> Result<Option<serde_json::Value>> {
+        if let Some(arg) = &schema.arg {
+            let Some(values) = self.matches.get_many::<String>(arg) else {
+                return Ok(None);
+            };
+            let values: Vec<String> = values.cloned().collect();
+            let value = ArgType::from(schema.type_.as_str())
+                .to_json(&values)
+                .and_then(|value| shorthand::conform(value, &schema, classes))
+                .map_err(|e| InvalidInput {
+                    message: format!(r#"invalid value "{}": {e}"#, values.join(" ")),
+                })?;
+            return Ok(Some(value));
+        }
+        if schema.type_ != "object" {
+            bail!(
+                r#"schema "{}" lacks the "arg" in the schema"#,
+                schema.name.unwrap_or("".to_string())
+            );
+        }
+        let Some(props) = &schema.props else {
+            bail!(r#"object schema lacks both the "arg" and "props" in the schema"#);
+        };
+        let mut map = serde_json::Map::new();
+        for prop in props {
+            if let Some(prop_name) = &prop.name {
+                let value = self.build_value(prop.clone(), classes)?;
+                if let Some(value) = value {
+                    map.insert(prop_name.clone(), value);
                 }
+            } else {
+                bail!(r#"property lacks the "name" in the schema"#,);
             }
-            "boolean" => {
-                if let Some(arg) = &schema.arg {
-                    if let Some(value) = self.matches.get_one::<bool>(arg) {
-                        Ok(Some((*value).into()))
-                    } else {
-                        Ok(None)
-                    }
-                } else {
-                    bail!(
-                        r#"schema "{}" lacks the "arg" in the schema"#,
-                        schema.name.unwrap_or("".to_string())
-                    );
+        }
+        if map.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(serde_json::Value::Object(map)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Command as ClapCommand;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn command(outputs: Value) -> Command {
+        serde_json::from_value(json!({
+            "name": "show",
+            "version": "2024-07-01",
+            "resources": [],
+            "argGroups": [],
+            "operations": [{
+                "http": {
+                    "path": "/foo",
+                    "request": {"method": "get", "path": {"params": []}, "query": {"consts": []}},
+                    "responses": [{
+                        "statusCode": [200],
+                        "body": {"json": {"var": "$Instance", "schema": {"type": "@Foo_read"}}}
+                    }, {
+                        "statusCode": [201],
+                        "body": {"json": {"schema": {
+                            "type": "object",
+                            "cls": "Foo_read",
+                            "props": [
+                                {"type": "string", "name": "name"},
+                                {"type": "object", "name": "properties", "clientFlatten": true, "props": [
+                                    {"type": "string", "name": "provisioningState"}
+                                ]}
+                            ]
+                        }}}
+                    }]
                 }
-            }
-            // TODO: We shall handle float and other potential types in the metadata.
-            //       Then fail the other cases.
-            _ => {
-                // We suppose any other type as a json value first, if failed, try to parse it as a string
-                if let Some(arg) = &schema.arg {
-                    if let Some(value) = self.matches.get_one::<String>(arg) {
-                        match serde_json::from_str(value) {
-                            Ok(v) => Ok(Some(v)),
-                            Err(_) => Ok(Some(serde_json::Value::String(value.clone()))),
-                        }
-                    } else {
-                        Ok(None)
-                    }
-                } else {
-                    bail!(
-                        r#"schema "{}" lacks the "arg" in the schema"#,
-                        schema.name.unwrap_or("".to_string())
-                    );
+            }],
+            "outputs": outputs,
+        }))
+        .unwrap()
+    }
+
+    fn new_invocation(outputs: Value) -> CommandInvocation {
+        CommandInvocation::new(
+            &command(outputs),
+            &ClapCommand::new("show").get_matches_from(["show"]),
+        )
+    }
+
+    #[test]
+    fn page_token() {
+        let token = PageToken {
+            next_link: Some(
+                "https://management.azure.com/foo?api-version=1&$skiptoken=x".to_string(),
+            ),
+            offset: 3,
+        };
+        assert_eq!(PageToken::decode(&token.encode().unwrap()).unwrap(), token);
+        assert!(PageToken::decode("not-a-token").is_err());
+    }
+
+    #[test]
+    fn paging() {
+        let mut operation: Operation =
+            serde_json::from_value(json!({"operationId": "Foo_List"})).unwrap();
+        let matches = ClapCommand::new("list").get_matches_from(["list"]);
+        let invocation = OperationInvocation::new(&operation, &matches);
+        assert_eq!(
+            invocation.paging(&json!({"value": [], "nextLink": null})),
+            Some(("nextLink".to_string(), "value".to_string()))
+        );
+        assert_eq!(invocation.paging(&json!({"value": []})), None);
+
+        operation.pageable =
+            Some(serde_json::from_value(json!({"nextLinkName": "@odata.nextLink"})).unwrap());
+        let invocation = OperationInvocation::new(&operation, &matches);
+        assert_eq!(
+            invocation.paging(&json!({"value": []})),
+            Some(("@odata.nextLink".to_string(), "value".to_string()))
+        );
+    }
+
+    #[test]
+    fn poller_options() {
+        let mut operation: Operation =
+            serde_json::from_value(json!({"operationId": "Foo_Get"})).unwrap();
+        let matches = ClapCommand::new("show").get_matches_from(["show"]);
+        let invocation = OperationInvocation::new(&operation, &matches);
+        assert_eq!(invocation.poller_options().unwrap(), None);
+
+        operation.long_running = Some(
+            serde_json::from_value(
+                json!({"finalStateVia": "operation-location", "resultPath": "result"}),
+            )
+            .unwrap(),
+        );
+        let invocation = OperationInvocation::new(&operation, &matches);
+        assert_eq!(
+            invocation.poller_options().unwrap(),
+            Some(NewPollerOptions {
+                final_state: Some(FinalStateVia::OperationLocation),
+                operation_location_result_path: Some("result".to_string()),
+            })
+        );
+
+        operation.long_running =
+            Some(serde_json::from_value(json!({"finalStateVia": "somewhere"})).unwrap());
+        let invocation = OperationInvocation::new(&operation, &matches);
+        assert!(invocation.poller_options().is_err());
+    }
+
+    #[test]
+    fn build_outputs() {
+        let mut vars = Variables::default();
+        vars.insert(
+            "$Instance",
+            json!({"name": "foo", "properties": {"provisioningState": "Succeeded"}}),
+        );
+
+        let invocation =
+            new_invocation(json!([{"type": "object", "ref": "$Instance", "clientFlatten": true}]));
+        assert_eq!(
+            invocation.build_outputs(&vars),
+            Some(json!({"name": "foo", "provisioningState": "Succeeded"}))
+        );
+        let schema = invocation.output_schema().unwrap();
+        let props: Vec<_> = schema
+            .props
+            .unwrap()
+            .into_iter()
+            .filter_map(|p| p.name)
+            .collect();
+        assert_eq!(props, vec!["name", "provisioningState"]);
+
+        let invocation = new_invocation(json!([{"type": "object", "ref": "$Instance.properties"}]));
+        assert_eq!(
+            invocation.build_outputs(&vars),
+            Some(json!({"provisioningState": "Succeeded"}))
+        );
+
+        let invocation = new_invocation(Value::Null);
+        assert_eq!(invocation.build_outputs(&vars), None);
+    }
+
+    #[test]
+    fn flatten_recursive_schema() {
+        let vnet: Schema = serde_json::from_value(json!({
+            "type": "object",
+            "cls": "VNet",
+            "props": [{"type": "array", "name": "subnets", "item": {
+                "type": "object",
+                "props": [{"type": "@VNet", "name": "vnet"}]
+            }}]
+        }))
+        .unwrap();
+        let classes = HashMap::from([("VNet".to_string(), vnet.clone())]);
+        let schema = flatten_schema(&vnet, &classes);
+        let item = schema.props.unwrap()[0].item.clone().unwrap();
+        assert_eq!(item.props.unwrap()[0].type_, "@VNet");
+    }
+
+    #[test]
+    fn update_instance() {
+        let command: Command = serde_json::from_value(json!({
+            "name": "update",
+            "version": "2024-11-01",
+            "resources": [],
+            "argGroups": [],
+            "operations": [{"instanceUpdate": {"ref": "$Instance", "json": {"schema": {
+                "type": "object",
+                "props": [
+                    {"type": "ResourceLocation", "name": "location", "arg": "$parameters.location"},
+                    {"type": "object", "name": "tags", "arg": "$parameters.tags", "additionalProps": {"item": {"type": "string"}}}
+                ]
+            }}}}]
+        }))
+        .unwrap();
+        let matches = ClapCommand::new("update")
+            .args([
+                clap::Arg::new("$parameters.location").long("location"),
+                clap::Arg::new("$parameters.tags").long("tags"),
+                clap::Arg::new("set").long("set").num_args(1..),
+            ])
+            .get_matches_from([
+                "update",
+                "--tags",
+                r#"{"a":"b"}"#,
+                "--set",
+                "location=westus",
+            ]);
+        let invocation = CommandInvocation::new(&command, &matches);
+        let mut vars = Variables::default();
+        vars.insert(
+            "$Instance",
+            json!({"id": "/x", "location": "eastus", "tags": {"c": "d"}}),
+        );
+        let operation = &command.operations[0];
+        invocation
+            .update_instance(
+                operation,
+                operation.instance_update.as_ref().unwrap(),
+                &GenericUpdate::from_matches(&matches).unwrap(),
+                &command.schema_classes(),
+                &mut vars,
+            )
+            .unwrap();
+        assert_eq!(
+            vars.get("$Instance"),
+            Some(&json!({"id": "/x", "location": "westus", "tags": {"a": "b"}}))
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        let operator: ConditionOperator = serde_json::from_value(json!({
+            "type": "and",
+            "operators": [
+                {"type": "hasValue", "arg": "$Path.subscriptionId"},
+                {"type": "not", "operator": {"type": "hasValue", "arg": "$Path.resourceGroupName"}}
+            ]
+        }))
+        .unwrap();
+        let cmd = ClapCommand::new("list").args([
+            clap::Arg::new("$Path.subscriptionId").long("subscription"),
+            clap::Arg::new("$Path.resourceGroupName").long("resource-group"),
+        ]);
+        let invocation = |args: &[&str]| {
+            CommandInvocation::new(
+                &command(Value::Null),
+                &cmd.clone().get_matches_from([&["list"], args].concat()),
+            )
+        };
+        assert!(invocation(&["--subscription", "sub"]).evaluate(&operator));
+        assert!(
+            !invocation(&["--subscription", "sub", "--resource-group", "rg"]).evaluate(&operator)
+        );
+
+        let mut vars = Variables::default();
+        vars.insert("$Condition_List", Value::Bool(false));
+        let mut operation: Operation =
+            serde_json::from_value(json!({"operationId": "Foo_List"})).unwrap();
+        let invocation = invocation(&[]);
+        assert!(invocation.should_run(&operation, &vars).unwrap());
+        operation.when = Some(vec!["$Condition_List".to_string()]);
+        assert!(!invocation.should_run(&operation, &vars).unwrap());
+        vars.insert("$Condition_List", Value::Bool(true));
+        assert!(invocation.should_run(&operation, &vars).unwrap());
+        operation.when = Some(vec!["$Condition_Unknown".to_string()]);
+        assert!(invocation.should_run(&operation, &vars).is_err());
+    }
+
+    #[test]
+    fn query_pairs() {
+        let query: metadata::RequestQuery = serde_json::from_value(json!({
+            "params": [
+                {"type": "string", "name": "$expand", "arg": "$Query.expand"},
+                {"type": "string", "name": "$filter", "arg": "$Query.filter"},
+                {"type": "integer32", "name": "$top", "arg": "$Query.top", "required": true}
+            ],
+            "consts": [{"readOnly": true, "const": true, "default": {"value": "2024-07-01"}, "type": "string", "name": "api-version", "required": true}]
+        }))
+        .unwrap();
+        let operation: Operation =
+            serde_json::from_value(json!({"operationId": "Foo_List"})).unwrap();
+        let cmd = ClapCommand::new("list").args([
+            clap::Arg::new("$Query.expand").long("expand"),
+            clap::Arg::new("$Query.filter").long("filter"),
+            clap::Arg::new("$Query.top").long("top"),
+        ]);
+
+        let matches = cmd
+            .clone()
+            .get_matches_from(["list", "--top", "10", "--expand", "subnets"]);
+        assert_eq!(
+            OperationInvocation::new(&operation, &matches)
+                .query_pairs(&query)
+                .unwrap(),
+            vec![
+                ("$expand".to_string(), "subnets".to_string()),
+                ("$top".to_string(), "10".to_string()),
+                ("api-version".to_string(), "2024-07-01".to_string()),
+            ]
+        );
+
+        let matches = cmd.get_matches_from(["list"]);
+        assert_eq!(
+            OperationInvocation::new(&operation, &matches)
+                .query_pairs(&query)
+                .unwrap_err()
+                .to_string(),
+            "missing required query parameter: $top"
+        );
+    }
+
+    #[test]
+    fn build_path_and_headers() {
+        let http: metadata::Http = serde_json::from_value(json!({
+            "path": "/subscriptions/{subscriptionId}/providers/Microsoft.Foo/foos/{fooName}",
+            "request": {
+                "method": "get",
+                "path": {"params": [
+                    {"type": "string", "name": "subscriptionId", "arg": "$Path.subscriptionId", "required": true},
+                    {"type": "string", "name": "fooName", "arg": "$Path.fooName"}
+                ]},
+                "query": {"consts": []},
+                "header": {
+                    "params": [{"type": "string", "name": "If-Match", "arg": "$Header.ifMatch"}],
+                    "consts": [{"const": true, "default": {"value": "bar"}, "type": "string", "name": "x-ms-foo"}]
                 }
-            }
-        }
+            },
+            "responses": []
+        }))
+        .unwrap();
+        let operation: Operation =
+            serde_json::from_value(json!({"operationId": "Foo_Get"})).unwrap();
+        let cmd = ClapCommand::new("show").args([
+            clap::Arg::new("$Path.subscriptionId").long("subscription"),
+            clap::Arg::new("$Path.fooName").long("name"),
+            clap::Arg::new("$Header.ifMatch").long("if-match"),
+        ]);
+
+        let matches = cmd.clone().get_matches_from([
+            "show",
+            "--subscription",
+            "sub",
+            "--name",
+            "foo bar#1?%/",
+            "--if-match",
+            "*",
+        ]);
+        let invocation = OperationInvocation::new(&operation, &matches);
+        assert_eq!(
+            invocation.build_path(&http).unwrap(),
+            "/subscriptions/sub/providers/Microsoft.Foo/foos/foo%20bar%231%3F%25%2F"
+        );
+        assert_eq!(
+            invocation
+                .headers(http.request.header.as_ref().unwrap())
+                .unwrap(),
+            vec![
+                ("If-Match".to_string(), "*".to_string()),
+                ("x-ms-foo".to_string(), "bar".to_string()),
+            ]
+        );
+
+        let matches = cmd
+            .clone()
+            .get_matches_from(["show", "--subscription", "sub"]);
+        let invocation = OperationInvocation::new(&operation, &matches);
+        assert_eq!(
+            invocation.build_path(&http).unwrap(),
+            "/subscriptions/sub/providers/Microsoft.Foo/foos"
+        );
+        assert_eq!(
+            invocation
+                .headers(http.request.header.as_ref().unwrap())
+                .unwrap(),
+            vec![("x-ms-foo".to_string(), "bar".to_string())]
+        );
+
+        let matches = cmd.get_matches_from(["show"]);
+        assert_eq!(
+            OperationInvocation::new(&operation, &matches)
+                .build_path(&http)
+                .unwrap_err()
+                .to_string(),
+            "missing required path parameter: subscriptionId"
+        );
     }
 }
@@ -1,7 +1,10 @@
 // TODO: This is not a comprehensive definition for the API metadata, just for PoC.
 use anyhow::{anyhow, bail, Result};
 use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
 
+use crate::api::types::ArgType;
 use crate::arg::CliInput;
 
 #[cfg_attr(test, derive(serde::Serialize))]
@@ -67,6 +70,26 @@ pub struct Command {
     pub operations: Vec<Operation>,
     pub outputs: Option<Vec<Output>>,
     pub confirmation: Option<String>,
+    pub conditions: Option<Vec<Condition>>,
+}
+
+// Condition is evaluated against the arguments before running the operations, and bound to the variable
+// (e.g. "$Condition_Foo_Get"), which is referenced by the "when" of the operations.
+#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Condition {
+    pub var: String,
+    pub operator: ConditionOperator,
+}
+
+#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ConditionOperator {
+    And { operators: Vec<ConditionOperator> },
+    Or { operators: Vec<ConditionOperator> },
+    Not { operator: Box<ConditionOperator> },
+    HasValue { arg: String },
 }
 
 #[cfg_attr(test, derive(serde::Serialize))]
@@ -98,6 +121,23 @@ pub struct Arg {
     pub id_part: Option<String>,
     #[serde(rename = "additionalProps")]
     pub additional_props: Option<AdditionalPropSchema>,
+    pub format: Option<RequestFormat>,
+    #[serde(rename = "enum")]
+    pub enum_: Option<Enum>,
+}
+
+// Enum lists the allowed values of an argument.
+#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Enum {
+    pub items: Vec<EnumItem>,
+}
+
+#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnumItem {
+    pub name: Option<String>,
+    pub value: Value,
 }
 
 #[cfg_attr(test, derive(serde::Serialize))]
@@ -112,6 +152,46 @@ pub struct Operation {
     #[serde(rename = "operationId")]
     pub operation_id: Option<String>,
     pub http: Option<Http>,
+    #[serde(rename = "longRunning")]
+    pub long_running: Option<LongRunning>,
+    pub pageable: Option<Pageable>,
+    #[serde(rename = "instanceUpdate")]
+    pub instance_update: Option<InstanceUpdate>,
+    // The condition variables that must all hold for this operation to run.
+    pub when: Option<Vec<String>>,
+}
+
+// InstanceUpdate updates the instance bound to the variable in place, instead of sending a request.
+// This is used by the generic update commands, between the GET and the PUT/PATCH of the resource.
+#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstanceUpdate {
+    #[serde(rename = "ref")]
+    pub ref_: String,
+    pub json: BodyJSON,
+}
+
+#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct LongRunning {
+    // How to get the final result of the LRO, e.g. "azure-async-operation", "location", "original-uri".
+    #[serde(rename = "finalStateVia")]
+    pub final_state_via: Option<String>,
+    // The JSON path to the result in the terminal response of an Operation-Location LRO.
+    #[serde(rename = "resultPath")]
+    pub result_path: Option<String>,
+}
+
+// Pageable describes a list operation, whose result spans multiple pages linked by the next link.
+#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Pageable {
+    // The name of the property that contains the next link, defaults to "nextLink".
+    #[serde(rename = "nextLinkName")]
+    pub next_link_name: Option<String>,
+    // The name of the property that contains the items of a page, defaults to "value".
+    #[serde(rename = "itemName")]
+    pub item_name: Option<String>,
 }
 
 #[cfg_attr(test, derive(serde::Serialize))]
@@ -128,6 +208,7 @@ pub struct Request {
     pub method: Method,
     pub path: RequestPath,
     pub query: RequestQuery,
+    pub header: Option<RequestHeader>,
     pub body: Option<Body>,
 }
 
@@ -148,28 +229,79 @@ pub struct RequestPathParam {
     pub format: Option<RequestFormat>,
 }
 
+// RequestFormat constrains the value of an argument, which is validated before sending the request.
 #[cfg_attr(test, derive(serde::Serialize))]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 pub struct RequestFormat {
-    pub pattern: String,
+    pub pattern: Option<String>,
     #[serde(rename = "maxLength")]
-    pub max_length: i64,
+    pub max_length: Option<i64>,
     #[serde(rename = "minLength")]
-    pub min_length: i64,
+    pub min_length: Option<i64>,
+    pub maximum: Option<f64>,
+    pub minimum: Option<f64>,
 }
 
+// SchemaFormat is the format of a schema property, i.e. the template of a response property (e.g. the resource id),
+// or the constraints of a request property.
 #[cfg_attr(test, derive(serde::Serialize))]
 #[derive(Debug, Clone, Deserialize)]
-pub struct ResponseFormat {
+pub struct SchemaFormat {
     pub template: Option<String>,
+    #[serde(flatten)]
+    pub constraints: RequestFormat,
 }
 
 #[cfg_attr(test, derive(serde::Serialize))]
 #[derive(Debug, Clone, Deserialize)]
 pub struct RequestQuery {
+    pub params: Option<Vec<RequestQueryParam>>,
     pub consts: Vec<RequestQueryConst>,
 }
 
+#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct RequestQueryParam {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub name: String,
+    pub arg: String,
+    pub required: Option<bool>,
+    pub format: Option<RequestFormat>,
+}
+
+#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct RequestHeader {
+    pub params: Option<Vec<RequestHeaderParam>>,
+    pub consts: Option<Vec<RequestHeaderConst>>,
+}
+
+#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct RequestHeaderParam {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub name: String,
+    pub arg: String,
+    pub required: Option<bool>,
+    pub format: Option<RequestFormat>,
+}
+
+#[cfg_attr(test, derive(serde::Serialize))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct RequestHeaderConst {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub name: String,
+    pub required: Option<bool>,
+    #[serde(rename = "readOnly")]
+    pub read_only: Option<bool>,
+    #[serde(rename = "const")]
+    pub const_: bool,
+    pub default: DefaultValue,
+}
+
 #[cfg_attr(test, derive(serde::Serialize))]
 #[derive(Debug, Clone, Deserialize)]
 pub struct RequestQueryConst {
@@ -228,7 +360,7 @@ pub struct Output {
 }
 
 #[cfg_attr(test, derive(serde::Serialize))]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 pub struct Schema {
     #[serde(rename = "type")]
     pub type_: String,
@@ -239,11 +371,15 @@ pub struct Schema {
     pub read_only: Option<bool>,
     pub props: Option<Vec<Schema>>,
     pub item: Option<Box<Schema>>,
-    pub format: Option<ResponseFormat>,
+    pub format: Option<SchemaFormat>,
+    #[serde(rename = "enum")]
+    pub enum_: Option<Enum>,
     #[serde(rename = "clientFlatten")]
     pub client_flatten: Option<bool>,
     #[serde(rename = "additionalProps")]
     pub additional_props: Option<AdditionalPropSchema>,
+    // The class name of this schema, which can be referenced by other schemas via type "@<cls>".
+    pub cls: Option<String>,
 }
 
 #[cfg_attr(test, derive(serde::Serialize))]
@@ -259,6 +395,247 @@ pub struct AdditionalPropItemSchema {
     pub type_: String,
 }
 
+impl Command {
+    // schema_classes returns all the schema classes (i.e. schemas that have "cls") defined in this command,
+    // keyed by the class name.
+    pub fn schema_classes(&self) -> HashMap<String, Schema> {
+        let mut classes = HashMap::new();
+        for op in &self.operations {
+            classes.extend(op.schema_classes());
+        }
+        classes
+    }
+
+    // body_args returns the arguments bound to the request bodies of the operations, or None if no operation sends
+    // a body built from the arguments.
+    pub fn body_args(&self) -> Option<HashSet<&str>> {
+        fn collect<'a>(schema: &'a Schema, args: &mut HashSet<&'a str>) {
+            args.extend(schema.arg.as_deref());
+            schema
+                .props
+                .iter()
+                .flatten()
+                .for_each(|prop| collect(prop, args));
+        }
+
+        let mut schemas = self
+            .operations
+            .iter()
+            .filter_map(|op| {
+                op.http
+                    .as_ref()?
+                    .request
+                    .body
+                    .as_ref()?
+                    .json
+                    .schema
+                    .as_ref()
+            })
+            .peekable();
+        schemas.peek()?;
+        let mut args = HashSet::new();
+        schemas.for_each(|schema| collect(schema, &mut args));
+        Some(args)
+    }
+
+    // id_args returns the arguments that are parts of the resource id, e.g. "--subscription" and "-g".
+    pub fn id_args(&self) -> impl Iterator<Item = &Arg> {
+        self.arg_groups
+            .iter()
+            .flat_map(|ag| &ag.args)
+            .filter(|arg| arg.id_part.is_some())
+    }
+
+    // subscription_args returns the arguments of the subscription id, which default to the subscription set by
+    // "account set".
+    pub fn subscription_args(&self) -> impl Iterator<Item = &Arg> {
+        self.arg_groups
+            .iter()
+            .flat_map(|ag| &ag.args)
+            .filter(|arg| ArgType::from(arg.type_.as_str()) == ArgType::SubscriptionId)
+    }
+
+    // parse_id parses the resource id against the resource templates of the command, and returns the arguments of
+    // the id parts along with their values.
+    pub fn parse_id(&self, id: &str) -> Result<Vec<(&Arg, String)>> {
+        let Some(parts) = self.resources.iter().find_map(|r| r.parse_id(id)) else {
+            let templates: Vec<&str> = self.resources.iter().map(|r| r.id.as_str()).collect();
+            bail!(
+                r#"invalid resource id "{id}", expect the format "{}""#,
+                templates.join(r#"" or ""#)
+            );
+        };
+        Ok(self
+            .id_args()
+            .filter_map(|arg| Some((arg, parts.get(arg.id_part.as_deref()?)?.clone())))
+            .collect())
+    }
+
+    // long_running tells whether the command runs any long running operation.
+    pub fn long_running(&self) -> bool {
+        self.operations.iter().any(|op| op.long_running.is_some())
+    }
+
+    // instance_update returns the instance update operation of a generic update command.
+    pub fn instance_update(&self) -> Option<&InstanceUpdate> {
+        self.operations
+            .iter()
+            .find_map(|op| op.instance_update.as_ref())
+    }
+
+    // var_schema returns the schema of the response body that is bound to the variable (e.g. "$Instance").
+    pub fn var_schema(&self, var: &str) -> Option<&Schema> {
+        self.operations
+            .iter()
+            .filter_map(|op| op.http.as_ref())
+            .flat_map(|http| http.responses.iter())
+            .filter(|resp| resp.is_error != Some(true))
+            .filter_map(|resp| resp.body.as_ref())
+            .filter(|body| body.json.var.as_deref() == Some(var))
+            .find_map(|body| body.json.schema.as_ref())
+    }
+}
+
+impl Arg {
+    // option_name returns the option that is shown to the user for the argument, which prefers the long one,
+    // e.g. "--resource-group" for "-g".
+    pub fn option_name(&self) -> String {
+        match self.options.iter().find(|opt| opt.len() > 1) {
+            Some(long) => format!("--{long}"),
+            None => format!(
+                "-{}",
+                self.options.first().map(String::as_str).unwrap_or_default()
+            ),
+        }
+    }
+}
+
+impl Resource {
+    // parse_id parses the resource id against the id template (e.g. "/subscriptions/{}/resourcegroups/{}"), and
+    // returns the id parts keyed by their names, i.e. "subscription", "resource_group", "name", "child_name_1", etc.
+    pub fn parse_id(&self, id: &str) -> Option<HashMap<String, String>> {
+        let template: Vec<&str> = self.id.trim_matches('/').split('/').collect();
+        let segments: Vec<&str> = id.trim_matches('/').split('/').collect();
+        if template.len() != segments.len() {
+            return None;
+        }
+        let mut parts = HashMap::new();
+        let mut names = 0;
+        for (i, (t, segment)) in template.iter().zip(&segments).enumerate() {
+            if *t != "{}" {
+                if !t.eq_ignore_ascii_case(segment) {
+                    return None;
+                }
+                continue;
+            }
+            if segment.is_empty() {
+                return None;
+            }
+            let part = match template[..i].last().map(|t| t.to_lowercase()).as_deref() {
+                Some("subscriptions") => "subscription".to_string(),
+                Some("resourcegroups") => "resource_group".to_string(),
+                _ => {
+                    names += 1;
+                    match names {
+                        1 => "name".to_string(),
+                        n => format!("child_name_{}", n - 1),
+                    }
+                }
+            };
+            parts.insert(part, segment.to_string());
+        }
+        Some(parts)
+    }
+}
+
+impl Operation {
+    // is_pageable tells whether the operation returns the items in pages, which is either declared by the metadata,
+    // or a GET with the conventional "value" array in its response, as detected by the invocation.
+    pub fn is_pageable(&self) -> bool {
+        if self.pageable.is_some() {
+            return true;
+        }
+        let Some(http) = &self.http else {
+            return false;
+        };
+        matches!(http.request.method, Method::Get)
+            && http
+                .responses
+                .iter()
+                .filter(|resp| resp.is_error != Some(true))
+                .filter_map(|resp| resp.body.as_ref()?.json.schema.as_ref())
+                .flat_map(|schema| schema.props.iter().flatten())
+                .any(|prop| {
+                    prop.name.as_deref() == Some("value") && prop.type_.starts_with("array")
+                })
+    }
+
+    // schema_classes returns the schema classes defined in the request and response bodies of this operation,
+    // keyed by the class name.
+    pub fn schema_classes(&self) -> HashMap<String, Schema> {
+        fn collect(schema: &Schema, classes: &mut HashMap<String, Schema>) {
+            if let Some(cls) = &schema.cls {
+                classes.insert(cls.clone(), schema.clone());
+            }
+            schema
+                .props
+                .iter()
+                .flatten()
+                .for_each(|prop| collect(prop, classes));
+            if let Some(item) = &schema.item {
+                collect(item, classes);
+            }
+        }
+
+        let mut bodies: Vec<&BodyJSON> = vec![];
+        if let Some(http) = &self.http {
+            bodies.extend(http.request.body.iter().map(|body| &body.json));
+            bodies.extend(
+                http.responses
+                    .iter()
+                    .filter_map(|resp| resp.body.as_ref().map(|body| &body.json)),
+            );
+        }
+        if let Some(instance_update) = &self.instance_update {
+            bodies.push(&instance_update.json);
+        }
+        let mut classes = HashMap::new();
+        for schema in bodies.into_iter().filter_map(|json| json.schema.as_ref()) {
+            collect(schema, &mut classes);
+        }
+        classes
+    }
+}
+
+impl Schema {
+    // resolve returns the schema with its class reference (i.e. type "@<cls>") replaced by the class definition.
+    // The schema itself is returned if it is not a class reference, or the class is not defined.
+    pub fn resolve(&self, classes: &HashMap<String, Schema>) -> Schema {
+        let Some(cls) = self.type_.strip_prefix('@') else {
+            return self.clone();
+        };
+        let Some(def) = classes.get(cls) else {
+            return self.clone();
+        };
+        Schema {
+            type_: def.type_.clone(),
+            props: def.props.clone(),
+            item: def.item.clone(),
+            additional_props: def.additional_props.clone(),
+            format: self.format.clone().or(def.format.clone()),
+            enum_: self.enum_.clone().or(def.enum_.clone()),
+            ..self.clone()
+        }
+    }
+
+    pub fn prop(&self, name: &str) -> Option<&Schema> {
+        self.props
+            .as_ref()?
+            .iter()
+            .find(|p| p.name.as_deref() == Some(name))
+    }
+}
+
 impl Metadata {
     pub fn resolve_command(&self, input: &CliInput) -> Result<Command> {
         if input.is_empty() {
@@ -298,7 +675,7 @@ impl Metadata {
             }
         }
 
-        return Err(anyhow!("this isn't a command"));
+        Err(anyhow!("this isn't a command"))
     }
 }
 
@@ -779,4 +1156,81 @@ mod test {
         assert_eq!(input_json, output_json);
         Ok(())
     }
+
+    #[test]
+    fn parse_id() {
+        let command: Command = serde_json::from_value(serde_json::json!({
+            "name": "show",
+            "version": "2024-07-01",
+            "resources": [{
+                "id": "/subscriptions/{}/resourcegroups/{}/providers/microsoft.network/virtualnetworks/{}/subnets/{}",
+                "version": "2024-07-01",
+                "swagger": ""
+            }],
+            "argGroups": [{
+                "name": "",
+                "args": [
+                    {"type": "ResourceGroupName", "var": "$Path.resourceGroupName", "options": ["g", "resource-group"], "idPart": "resource_group"},
+                    {"type": "string", "var": "$Path.subnetName", "options": ["n", "name"], "idPart": "child_name_1"},
+                    {"type": "SubscriptionId", "var": "$Path.subscriptionId", "options": ["subscription"], "idPart": "subscription"},
+                    {"type": "string", "var": "$Path.virtualNetworkName", "options": ["vnet-name"], "idPart": "name"},
+                    {"type": "string", "var": "$Query.expand", "options": ["expand"]}
+                ]
+            }],
+            "operations": []
+        }))
+        .unwrap();
+        let parts = |id| {
+            command.parse_id(id).map(|parts| {
+                parts
+                    .into_iter()
+                    .map(|(arg, value)| (arg.options.last().unwrap().as_str(), value))
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(
+            parts("/subscriptions/sub/resourceGroups/rg/providers/Microsoft.Network/virtualNetworks/vnet/subnets/subnet").unwrap(),
+            vec![
+                ("resource-group", "rg".to_string()),
+                ("name", "subnet".to_string()),
+                ("subscription", "sub".to_string()),
+                ("vnet-name", "vnet".to_string()),
+            ]
+        );
+        assert_eq!(
+            parts("/subscriptions/sub/resourceGroups/rg/providers/Microsoft.Network/virtualNetworks/vnet")
+                .unwrap_err()
+                .to_string(),
+            r#"invalid resource id "/subscriptions/sub/resourceGroups/rg/providers/Microsoft.Network/virtualNetworks/vnet", expect the format "/subscriptions/{}/resourcegroups/{}/providers/microsoft.network/virtualnetworks/{}/subnets/{}""#
+        );
+        assert!(parts("/subscriptions/sub/resourceGroups/rg/providers/Microsoft.Compute/virtualNetworks/vnet/subnets/subnet").is_err());
+    }
+
+    #[test]
+    fn is_pageable() {
+        let operation = |method: &str, props: Value| -> Operation {
+            serde_json::from_value(serde_json::json!({
+                "http": {
+                    "path": "/subscriptions/{subscriptionId}/providers/Microsoft.Network/virtualNetworks",
+                    "request": {"method": method, "path": {"params": []}, "query": {"consts": []}},
+                    "responses": [
+                        {"statusCode": [200], "body": {"json": {"schema": {"type": "object", "props": props}}}},
+                        {"isError": true, "body": {"json": {"schema": {"type": "@MgmtErrorFormat"}}}}
+                    ]
+                }
+            }))
+            .unwrap()
+        };
+        let list_props = serde_json::json!([
+            {"type": "array", "name": "value", "item": {"type": "object"}},
+            {"type": "string", "name": "nextLink"}
+        ]);
+        assert!(operation("get", list_props.clone()).is_pageable());
+        assert!(!operation("post", list_props).is_pageable());
+        assert!(!operation(
+            "get",
+            serde_json::json!([{"type": "string", "name": "name"}])
+        )
+        .is_pageable());
+    }
 }
@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value};
+
+use super::metadata::Schema;
+use super::types::ArgType;
+
+/// Parses the value in the shorthand syntax into JSON, e.g. `{name:foo,tags:{env:dev},ports:[80,443]}`.
+///
+/// The scalars are parsed as strings, except `null`, which are converted by the schema of the target property via
+/// [`conform`]. A string can be quoted by single or double quotes to contain the special characters, e.g.
+/// `{name:'a,b'}`. JSON is accepted as well, whose scalars keep their types.
+pub fn parse(raw: &str) -> Result<Value> {
+    let trimmed = raw.trim_start();
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && let Ok(value) = serde_json::from_str(raw)
+    {
+        return Ok(value);
+    }
+    let mut parser = Parser {
+        chars: raw.chars().collect(),
+        pos: 0,
+    };
+    let value = parser.value(false)?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        bail!(
+            r#"invalid shorthand value "{raw}": unexpected "{}" at {}"#,
+            parser.chars[parser.pos],
+            parser.pos
+        );
+    }
+    Ok(value)
+}
+
+/// Parses the `key=value` pair of an object or dictionary argument, e.g. `env=dev` of `--tags env=dev team=core`.
+/// The value is in the shorthand syntax. It returns None if the raw value is not a pair.
+pub fn parse_pair(raw: &str) -> Option<Result<(String, Value)>> {
+    if raw.starts_with(['{', '[', '\'', '"']) {
+        return None;
+    }
+    let (key, value) = raw.split_once('=')?;
+    if key.is_empty() || key.contains([':', ',', '{', '[']) {
+        return None;
+    }
+    Some(parse(value).map(|value| (key.to_string(), value)))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(next) if next == c => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(next) => Err(anyhow!(r#"expect "{c}", found "{next}" at {}"#, self.pos)),
+            None => Err(anyhow!(r#"expect "{c}", found the end"#)),
+        }
+    }
+
+    // value parses a value, which is ended by the delimiters of the enclosing object or array, if any.
+    fn value(&mut self, nested: bool) -> Result<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('\'' | '"') => Ok(Value::String(self.quoted()?)),
+            _ => {
+                let bare = self.bare(if nested { ",}]" } else { "" });
+                match bare.as_str() {
+                    "null" => Ok(Value::Null),
+                    _ => Ok(Value::String(bare)),
+                }
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut map = Map::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::Object(map));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = match self.peek() {
+                Some('\'' | '"') => self.quoted()?,
+                _ => self.bare(":,}"),
+            };
+            if key.is_empty() {
+                bail!("expect a property name at {}", self.pos);
+            }
+            self.expect(':')?;
+            let value = self.value(true)?;
+            map.insert(key, value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                _ => break,
+            }
+        }
+        self.expect('}')?;
+        Ok(Value::Object(map))
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value(true)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                _ => break,
+            }
+        }
+        self.expect(']')?;
+        Ok(Value::Array(items))
+    }
+
+    // quoted parses a string quoted by single quotes, which is taken literally, or double quotes, which follows the
+    // escapes of JSON.
+    fn quoted(&mut self) -> Result<String> {
+        let quote = self.chars[self.pos];
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '\\' if quote == '"' => self.pos += 1,
+                c if c == quote => {
+                    let content: String = self.chars[start + 1..self.pos - 1].iter().collect();
+                    return match quote {
+                        '"' => Ok(serde_json::from_str(&format!("\"{content}\""))?),
+                        _ => Ok(content),
+                    };
+                }
+                _ => {}
+            }
+        }
+        bail!("unterminated string starting at {start}")
+    }
+
+    // bare parses an unquoted string till any of the delimiters, with the surrounding whitespaces trimmed.
+    fn bare(&mut self, delimiters: &str) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !delimiters.contains(c)) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+}
+
+/// Converts the parsed value by the schema of the target property, e.g. the string `"10"` becomes the number `10` for
+/// an integer property. The keys of an object are matched to the property names case insensitively, regardless of
+/// the hyphens and underscores, so both `addressPrefix` and `address-prefix` work. The properties of a client
+/// flattened property (e.g. "properties") can be specified at the parent level.
+pub fn conform(value: Value, schema: &Schema, classes: &HashMap<String, Schema>) -> Result<Value> {
+    let schema = schema.resolve(classes);
+    let type_ = ArgType::from(schema.type_.as_str());
+    match (value, type_) {
+        (Value::Null, _) => Ok(Value::Null),
+        (Value::Object(map), ArgType::Object) => {
+            let mut result = Map::new();
+            for (key, value) in map {
+                if let Some(path) = prop_path(&schema, &key, classes) {
+                    let (prop, parents) = path.split_last().unwrap();
+                    let mut object = &mut result;
+                    for parent in parents {
+                        let parent = object
+                            .entry(parent.name.clone().unwrap_or_default())
+                            .or_insert(Value::Object(Map::new()));
+                        if !parent.is_object() {
+                            *parent = Value::Object(Map::new());
+                        }
+                        object = parent.as_object_mut().unwrap();
+                    }
+                    let name = prop.name.clone().unwrap_or_default();
+                    match (object.get_mut(&name), conform(value, prop, classes)?) {
+                        // Merge with the properties specified at the parent level, if flattened
+                        (Some(Value::Object(existing)), Value::Object(value)) => {
+                            existing.extend(value)
+                        }
+                        (_, value) => {
+                            object.insert(name, value);
+                        }
+                    }
+                } else if let Some(item) = additional_item(&schema) {
+                    let value = conform(value, &item, classes)?;
+                    result.insert(key, value);
+                } else if schema.props.is_none() {
+                    // A free-form object
+                    result.insert(key, value);
+                } else {
+                    bail!(
+                        r#"unknown property "{key}", expect one of: {}"#,
+                        prop_names(&schema, classes).join(", ")
+                    );
+                }
+            }
+            Ok(Value::Object(result))
+        }
+        (Value::Array(items), ArgType::Array(_)) => {
+            let item = item_schema(&schema);
+            Ok(Value::Array(
+                items
+                    .into_iter()
+                    .map(|value| conform(value, &item, classes))
+                    .collect::<Result<_>>()?,
+            ))
+        }
+        (value, ArgType::Object) => bail!("invalid value {value}, expect an object"),
+        (value, ArgType::Array(_)) => bail!("invalid value {value}, expect an array"),
+        (Value::String(s), ArgType::String | ArgType::Other) => Ok(Value::String(s)),
+        (Value::String(s), type_) => type_.to_json(&[type_.normalize(&s)?]),
+        // The value of an unknown type is kept as is
+        (value, ArgType::Other) => Ok(value),
+        (value @ (Value::Object(_) | Value::Array(_)), _) => {
+            bail!("invalid value {value}, expect a {} value", schema.type_)
+        }
+        // The typed scalar of JSON is checked and converted as the string, e.g. 1 becomes "1" for a string property
+        (value, type_) => type_.to_json(&[type_.normalize(&value.to_string())?]),
+    }
+}
+
+// prop_path returns the property that matches the key, following the client flattened properties it is in.
+fn prop_path(schema: &Schema, key: &str, classes: &HashMap<String, Schema>) -> Option<Vec<Schema>> {
+    let props = schema.props.iter().flatten();
+    if let Some(prop) = props
+        .clone()
+        .find(|prop| prop.name.as_deref().is_some_and(|name| same_key(name, key)))
+    {
+        return Some(vec![prop.clone()]);
+    }
+    props
+        .filter(|prop| prop.client_flatten == Some(true))
+        .find_map(|prop| {
+            let mut path = prop_path(&prop.resolve(classes), key, classes)?;
+            path.insert(0, prop.clone());
+            Some(path)
+        })
+}
+
+// prop_names returns the names of the properties, including those of the client flattened properties.
+fn prop_names(schema: &Schema, classes: &HashMap<String, Schema>) -> Vec<String> {
+    let mut names = vec![];
+    for prop in schema.props.iter().flatten() {
+        names.extend(prop.name.clone());
+        if prop.client_flatten == Some(true) {
+            names.extend(prop_names(&prop.resolve(classes), classes));
+        }
+    }
+    names
+}
+
+fn same_key(name: &str, key: &str) -> bool {
+    let simplify = |s: &str| {
+        s.chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_lowercase()
+    };
+    name == key || simplify(name) == simplify(key)
+}
+
+// item_schema returns the schema of the array items, which is declared by "item" or the type (e.g. "array<string>").
+fn item_schema(schema: &Schema) -> Schema {
+    if let Some(item) = &schema.item {
+        return item.as_ref().clone();
+    }
+    let type_ = schema
+        .type_
+        .strip_prefix("array<")
+        .and_then(|t| t.strip_suffix('>'))
+        .unwrap_or("string");
+    Schema {
+        type_: type_.to_string(),
+        ..Default::default()
+    }
+}
+
+// additional_item returns the schema of the dictionary values, which is declared by "additionalProps" or the type
+// (e.g. "dict<string>"). It returns None if the object is not a dictionary.
+fn additional_item(schema: &Schema) -> Option<Schema> {
+    let type_ = match &schema.additional_props {
+        Some(additional_props) => additional_props.item.type_.clone(),
+        None => schema
+            .type_
+            .strip_prefix("dict<")
+            .and_then(|t| t.strip_suffix('>'))?
+            .to_string(),
+    };
+    Some(Schema {
+        type_,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn parse_shorthand() {
+        assert_eq!(parse("foo").unwrap(), json!("foo"));
+        assert_eq!(parse("null").unwrap(), json!(null));
+        assert_eq!(
+            parse("{name: foo, tags:{env:dev}, ports:[80,443], empty:[], gone:null}").unwrap(),
+            json!({"name": "foo", "tags": {"env": "dev"}, "ports": ["80", "443"], "empty": [], "gone": null})
+        );
+        assert_eq!(
+            parse(r#"{'a,b':'c:d}', "e": "f\"g", url:http://foo/bar}"#).unwrap(),
+            json!({"a,b": "c:d}", "e": "f\"g", "url": "http://foo/bar"})
+        );
+        assert_eq!(parse("[a, {b: c}]").unwrap(), json!(["a", {"b": "c"}]));
+        assert_eq!(parse(r#"{"a": 1}"#).unwrap(), json!({"a": 1}));
+        for raw in ["{a}", "{a:b", "[a,b", "{a:b}c", "{'a:b}"] {
+            assert!(parse(raw).is_err(), "{raw}");
+        }
+
+        assert_eq!(
+            parse_pair("env=dev").unwrap().unwrap(),
+            ("env".to_string(), json!("dev"))
+        );
+        assert_eq!(
+            parse_pair("a=[b,c]").unwrap().unwrap(),
+            ("a".to_string(), json!(["b", "c"]))
+        );
+        assert!(parse_pair("{a:b=c}").is_none());
+        assert!(parse_pair("foo").is_none());
+    }
+
+    #[test]
+    fn conform_to_schema() {
+        let schema: Schema = serde_json::from_value(json!({
+            "type": "object",
+            "props": [
+                {"type": "string", "name": "name"},
+                {"type": "integer32", "name": "flowTimeout"},
+                {"type": "array<@SubResource>", "name": "ipAllocations", "item": {"type": "@SubResource"}},
+                {"type": "object", "name": "tags", "additionalProps": {"item": {"type": "boolean"}}},
+                {"type": "object", "name": "sub", "cls": "SubResource", "props": [{"type": "string", "name": "id"}]},
+                {"type": "object", "name": "properties", "clientFlatten": true, "props": [
+                    {"type": "string", "name": "addressPrefix"},
+                    {"type": "boolean", "name": "enabled"}
+                ]}
+            ]
+        }))
+        .unwrap();
+        let classes = HashMap::from([(
+            "SubResource".to_string(),
+            schema.prop("sub").cloned().unwrap(),
+        )]);
+
+        let value =
+            parse("{name:foo,flow-timeout:10,ip_allocations:[{id:bar}],tags:{a:yes,b:null}}")
+                .unwrap();
+        assert_eq!(
+            conform(value, &schema, &classes).unwrap(),
+            json!({"name": "foo", "flowTimeout": 10, "ipAllocations": [{"id": "bar"}], "tags": {"a": true, "b": null}})
+        );
+        assert_eq!(
+            conform(
+                parse("{address-prefix:10.0.0.0/24,properties:{enabled:true}}").unwrap(),
+                &schema,
+                &classes
+            )
+            .unwrap(),
+            json!({"properties": {"addressPrefix": "10.0.0.0/24", "enabled": true}})
+        );
+        assert_eq!(
+            conform(parse("{foo:bar}").unwrap(), &schema, &classes)
+                .unwrap_err()
+                .to_string(),
+            r#"unknown property "foo", expect one of: name, flowTimeout, ipAllocations, tags, sub, properties, addressPrefix, enabled"#
+        );
+        assert!(conform(parse("{flowTimeout:x}").unwrap(), &schema, &classes).is_err());
+        assert!(conform(parse("{ipAllocations:x}").unwrap(), &schema, &classes).is_err());
+
+        // The values of a dictionary are checked against the item type
+        let schema: Schema = serde_json::from_value(json!({"type": "dict<string>"})).unwrap();
+        assert_eq!(
+            conform(parse("{a:b,c:[1,2]}").unwrap(), &schema, &classes)
+                .unwrap_err()
+                .to_string(),
+            r#"invalid value ["1","2"], expect a string value"#
+        );
+        assert_eq!(
+            conform(json!({"a": 1, "b": true}), &schema, &classes).unwrap(),
+            json!({"a": "1", "b": "true"})
+        );
+        let schema: Schema = serde_json::from_value(json!({
+            "type": "object",
+            "additionalProps": {"item": {"type": "integer32"}}
+        }))
+        .unwrap();
+        assert!(conform(parse("{a:{b:1}}").unwrap(), &schema, &classes).is_err());
+        assert!(conform(json!({"a": 1.5}), &schema, &classes).is_err());
+    }
+}
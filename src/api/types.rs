@@ -0,0 +1,339 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat};
+use serde_json::{Map, Value};
+
+use super::shorthand;
+
+/// ArgType is the type of an argument (or a schema) in the metadata, e.g. "integer32" or "array<string>".
+///
+/// The raw value of the argument is normalized when it is parsed from the command line (e.g. the location
+/// "East US" becomes "eastus"), then converted to JSON by the type when building the request. The objects and arrays
+/// can be specified in the shorthand syntax, see [`shorthand::parse`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgType {
+    String,
+    Integer32,
+    Integer64,
+    Float,
+    Boolean,
+    Date,
+    DateTime,
+    Time,
+    Duration,
+    Uuid,
+    ResourceLocation,
+    ResourceGroupName,
+    SubscriptionId,
+    Object,
+    Array(Box<ArgType>),
+    /// Any other type (e.g. a class reference "@SubResource_create"), which is JSON or otherwise a string.
+    Other,
+}
+
+impl From<&str> for ArgType {
+    fn from(type_: &str) -> Self {
+        if let Some(item) = type_
+            .strip_prefix("array<")
+            .and_then(|t| t.strip_suffix('>'))
+        {
+            return ArgType::Array(Box::new(ArgType::from(item)));
+        }
+        if type_.starts_with("dict<") {
+            return ArgType::Object;
+        }
+        match type_ {
+            "string" | "password" | "byte" | "ResourceId" => ArgType::String,
+            "integer32" => ArgType::Integer32,
+            "integer" | "integer64" => ArgType::Integer64,
+            "float" | "float32" | "float64" | "number" => ArgType::Float,
+            "boolean" => ArgType::Boolean,
+            "date" => ArgType::Date,
+            "date-time" | "dateTime" => ArgType::DateTime,
+            "time" => ArgType::Time,
+            "duration" => ArgType::Duration,
+            "uuid" => ArgType::Uuid,
+            "ResourceLocation" => ArgType::ResourceLocation,
+            "ResourceGroupName" => ArgType::ResourceGroupName,
+            "SubscriptionId" => ArgType::SubscriptionId,
+            "object" => ArgType::Object,
+            "array" => ArgType::Array(Box::new(ArgType::Other)),
+            _ => ArgType::Other,
+        }
+    }
+}
+
+impl ArgType {
+    // is_flag tells whether the argument can be specified without a value, e.g. "--enable" for "--enable true".
+    pub fn is_flag(&self) -> bool {
+        *self == ArgType::Boolean
+    }
+
+    // is_array tells whether the argument takes multiple values, e.g. "--address-prefixes 10.0.0.0/16 10.1.0.0/16".
+    pub fn is_array(&self) -> bool {
+        matches!(self, ArgType::Array(_))
+    }
+
+    /// Validates the raw value specified in the command line, and returns it in the normalized form.
+    /// Each value of an array is normalized by the item type, unless it is a JSON array of all the items.
+    pub fn normalize(&self, raw: &str) -> Result<String> {
+        let invalid = |expect: &str| anyhow!(r#"invalid value "{raw}", expect {expect}"#);
+        match self {
+            ArgType::String | ArgType::Other => Ok(raw.to_string()),
+            ArgType::Integer32 => raw
+                .parse::<i32>()
+                .map(|v| v.to_string())
+                .map_err(|_| invalid("a 32-bit integer")),
+            ArgType::Integer64 => raw
+                .parse::<i64>()
+                .map(|v| v.to_string())
+                .map_err(|_| invalid("an integer")),
+            ArgType::Float => raw
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .map(|v| v.to_string())
+                .ok_or(invalid("a number")),
+            ArgType::Boolean => match raw.to_lowercase().as_str() {
+                "true" | "yes" | "y" | "1" | "on" => Ok("true".to_string()),
+                "false" | "no" | "n" | "0" | "off" => Ok("false".to_string()),
+                _ => Err(invalid("true or false")),
+            },
+            ArgType::Date => NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+                .map(|d| d.to_string())
+                .map_err(|_| invalid("a date, e.g. 2025-01-31")),
+            ArgType::DateTime => parse_date_time(raw)
+                .ok_or(invalid("an RFC 3339 date-time, e.g. 2025-01-31T08:00:00Z")),
+            ArgType::Time => NaiveTime::parse_from_str(raw, "%H:%M:%S%.f")
+                .or_else(|_| NaiveTime::parse_from_str(raw, "%H:%M"))
+                .map(|t| t.to_string())
+                .map_err(|_| invalid("a time, e.g. 08:00:00")),
+            ArgType::Duration => {
+                parse_duration(raw).ok_or(invalid("an ISO 8601 duration, e.g. PT1H30M"))
+            }
+            ArgType::Uuid => uuid::Uuid::parse_str(raw)
+                .map(|id| id.hyphenated().to_string())
+                .map_err(|_| invalid("a UUID")),
+            ArgType::SubscriptionId => uuid::Uuid::parse_str(raw)
+                .map(|id| id.hyphenated().to_string())
+                .map_err(|_| invalid("a subscription id (UUID)")),
+            ArgType::ResourceLocation => {
+                let location: String = raw
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect::<String>()
+                    .to_lowercase();
+                match location.is_empty() {
+                    true => Err(invalid("a location, e.g. eastus")),
+                    false => Ok(location),
+                }
+            }
+            ArgType::ResourceGroupName => {
+                let valid = (1..=90).contains(&raw.chars().count())
+                    && raw
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || "-_.()".contains(c))
+                    && !raw.ends_with('.');
+                match valid {
+                    true => Ok(raw.to_string()),
+                    false => Err(invalid(
+                        "a resource group name of up to 90 alphanumerics, underscores, parentheses, hyphens and periods (except at the end)",
+                    )),
+                }
+            }
+            ArgType::Object => {
+                if let Some(pair) = shorthand::parse_pair(raw) {
+                    return pair.map(|_| raw.to_string());
+                }
+                match shorthand::parse(raw)? {
+                    Value::Object(_) | Value::Null => Ok(raw.to_string()),
+                    _ => Err(invalid("an object, e.g. {key:value}, or key=value")),
+                }
+            }
+            ArgType::Array(_) if raw == "null" => Ok(raw.to_string()),
+            ArgType::Array(item) => match raw.trim_start().starts_with('[') {
+                true => match shorthand::parse(raw)? {
+                    Value::Array(_) => Ok(raw.to_string()),
+                    _ => Err(invalid("an array, e.g. [a,b]")),
+                },
+                false => item.normalize(raw),
+            },
+        }
+    }
+
+    /// Converts the normalized values of the argument to JSON. An array takes all the values, so does an object,
+    /// which merges the `key=value` pairs and the objects in the shorthand syntax. The others take the first one.
+    pub fn to_json(&self, values: &[String]) -> Result<Value> {
+        let raw = values.first().ok_or(anyhow!("the argument has no value"))?;
+        match self {
+            ArgType::Integer32 | ArgType::Integer64 => Ok(Value::from(raw.parse::<i64>()?)),
+            ArgType::Float => Ok(Value::from(raw.parse::<f64>()?)),
+            ArgType::Boolean => Ok(Value::Bool(self.normalize(raw)? == "true")),
+            ArgType::Object => {
+                let mut object = Map::new();
+                for raw in values {
+                    if let Some(pair) = shorthand::parse_pair(raw) {
+                        let (key, value) = pair?;
+                        object.insert(key, value);
+                        continue;
+                    }
+                    match shorthand::parse(raw)? {
+                        Value::Object(map) => object.extend(map),
+                        Value::Null if values.len() == 1 => return Ok(Value::Null),
+                        _ => bail!(r#"invalid value "{raw}", expect an object"#),
+                    }
+                }
+                Ok(Value::Object(object))
+            }
+            ArgType::Array(_) if values.len() == 1 && raw == "null" => Ok(Value::Null),
+            ArgType::Array(_) if values.len() == 1 && raw.trim_start().starts_with('[') => {
+                match shorthand::parse(raw)? {
+                    Value::Array(items) => Ok(Value::Array(items)),
+                    _ => Err(anyhow!(r#"invalid value "{raw}", expect an array"#)),
+                }
+            }
+            ArgType::Array(item) => Ok(Value::Array(
+                values
+                    .iter()
+                    .map(|v| item.to_json(std::slice::from_ref(v)))
+                    .collect::<Result<_>>()?,
+            )),
+            ArgType::Other => match serde_json::from_str(raw) {
+                Ok(value) => Ok(value),
+                Err(_) if raw.trim_start().starts_with(['{', '[']) => shorthand::parse(raw),
+                Err(_) => Ok(Value::String(raw.to_string())),
+            },
+            _ => Ok(Value::String(raw.to_string())),
+        }
+    }
+}
+
+// parse_date_time parses the RFC 3339 date-time, the one without the offset is regarded as UTC.
+fn parse_date_time(raw: &str) -> Option<String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Some(dt.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+    }
+    let dt = NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S%.f"))
+        .ok()?;
+    Some(dt.and_utc().to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+// parse_duration validates the ISO 8601 duration (e.g. "P1DT2H", "PT0.5S"), and returns it in upper case.
+fn parse_duration(raw: &str) -> Option<String> {
+    let duration = raw.to_uppercase();
+    let rest = duration.strip_prefix('P')?;
+    let (date, time) = match rest.split_once('T') {
+        Some((date, time)) if !time.is_empty() => (date, Some(time)),
+        Some(_) => return None,
+        None => (rest, None),
+    };
+    // Each part is a sequence of numbers followed by the designators in order
+    let valid = |part: &str, designators: &str, fraction: bool| {
+        let mut designators = designators.chars();
+        let mut number = String::new();
+        for c in part.chars() {
+            if c.is_ascii_digit() || (fraction && c == '.') {
+                number.push(c);
+                continue;
+            }
+            if number.is_empty() || number.parse::<f64>().is_err() || !designators.any(|d| d == c) {
+                return false;
+            }
+            number.clear();
+        }
+        number.is_empty()
+    };
+    let valid = valid(date, "YMWD", false) && time.is_none_or(|t| valid(t, "HMS", true));
+    (valid && (!date.is_empty() || time.is_some())).then_some(duration)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn normalize() {
+        let ok = |type_: &str, raw: &str| ArgType::from(type_).normalize(raw).unwrap();
+        let err = |type_: &str, raw: &str| ArgType::from(type_).normalize(raw).is_err();
+
+        assert_eq!(ok("ResourceLocation", "East US 2"), "eastus2");
+        assert_eq!(ok("boolean", "Yes"), "true");
+        assert_eq!(ok("integer32", "10"), "10");
+        assert!(err("integer32", "4294967296"));
+        assert_eq!(ok("integer64", "4294967296"), "4294967296");
+        assert_eq!(ok("float64", "1.5"), "1.5");
+        assert!(err("float64", "NaN"));
+        assert_eq!(
+            ok("SubscriptionId", "0B1F6471-1BF0-4DDA-AEC3-111122223333"),
+            "0b1f6471-1bf0-4dda-aec3-111122223333"
+        );
+        assert!(err("SubscriptionId", "foo"));
+        assert_eq!(ok("ResourceGroupName", "my_rg(1)"), "my_rg(1)");
+        assert!(err("ResourceGroupName", "rg."));
+        assert!(err("ResourceGroupName", "rg/1"));
+        assert_eq!(
+            ok("date-time", "2025-01-31T08:00:00+08:00"),
+            "2025-01-31T08:00:00+08:00"
+        );
+        assert_eq!(
+            ok("date-time", "2025-01-31T08:00:00"),
+            "2025-01-31T08:00:00Z"
+        );
+        assert!(err("date-time", "yesterday"));
+        assert_eq!(ok("duration", "pt1h30m"), "PT1H30M");
+        assert_eq!(ok("duration", "P1DT0.5S"), "P1DT0.5S");
+        for raw in ["P", "PT", "P1H", "PT1D", "PT1M1H", "1H"] {
+            assert!(err("duration", raw), "{raw}");
+        }
+        assert_eq!(ok("array<integer32>", "1"), "1");
+        assert_eq!(ok("array<integer32>", "[1, 2]"), "[1, 2]");
+        assert!(err("array<integer32>", "a"));
+        assert!(err("object", "[]"));
+        assert_eq!(ok("object", "{a:b}"), "{a:b}");
+        assert_eq!(ok("object", "env=dev"), "env=dev");
+        assert!(err("object", "{a:b"));
+        assert_eq!(ok("array<string>", "[a,b]"), "[a,b]");
+    }
+
+    #[test]
+    fn to_json() {
+        let json = |type_: &str, values: &[&str]| {
+            let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+            ArgType::from(type_).to_json(&values).unwrap()
+        };
+        assert_eq!(json("string", &["10"]), json!("10"));
+        assert_eq!(json("integer32", &["10"]), json!(10));
+        assert_eq!(json("float32", &["0.5"]), json!(0.5));
+        assert_eq!(json("boolean", &["true"]), json!(true));
+        assert_eq!(json("ResourceLocation", &["eastus"]), json!("eastus"));
+        assert_eq!(json("object", &[r#"{"a": 1}"#]), json!({"a": 1}));
+        assert_eq!(
+            json("array<string>", &["10.0.0.0/16", "10.1.0.0/16"]),
+            json!(["10.0.0.0/16", "10.1.0.0/16"])
+        );
+        assert_eq!(json("array<string>", &[r#"["a", "b"]"#]), json!(["a", "b"]));
+        assert_eq!(
+            json("array<boolean>", &["true", "false"]),
+            json!([true, false])
+        );
+        assert_eq!(
+            json("array<@SubResource_create>", &[r#"{"id": "foo"}"#]),
+            json!([{"id": "foo"}])
+        );
+        assert_eq!(json("@Foo", &["foo"]), json!("foo"));
+        assert_eq!(
+            json("object", &["env=dev", "team=core", "{owner:me}"]),
+            json!({"env": "dev", "team": "core", "owner": "me"})
+        );
+        assert_eq!(json("object", &["null"]), json!(null));
+        assert_eq!(json("array<string>", &["[a, b]"]), json!(["a", "b"]));
+        assert_eq!(json("array<string>", &["null"]), json!(null));
+        assert_eq!(
+            json("array<object>", &["{name:a}", "{name:b}"]),
+            json!([{"name": "a"}, {"name": "b"}])
+        );
+        assert_eq!(json("@Foo", &["{id:foo}"]), json!({"id": "foo"}));
+    }
+}
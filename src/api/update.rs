@@ -0,0 +1,543 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use clap::ArgMatches;
+use serde_json::Value;
+
+use super::metadata::Schema;
+use super::shorthand;
+use super::types::ArgType;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Edit {
+    // Set the property at the path to the value.
+    Set {
+        path: Vec<Segment>,
+        value: String,
+    },
+    // Append the values to the list at the path.
+    Add {
+        path: Vec<Segment>,
+        values: Vec<String>,
+    },
+    // Remove the element at the index from the list at the path, or remove the property at the path.
+    Remove {
+        path: Vec<Segment>,
+        index: Option<usize>,
+    },
+}
+
+/// The edits specified by the generic update arguments (i.e. `--set`, `--add` and `--remove`), which are applied
+/// to the current instance in the order they appear in the command line.
+#[derive(Debug, Default)]
+pub struct GenericUpdate {
+    edits: Vec<(usize, Edit)>,
+    force_string: bool,
+}
+
+impl GenericUpdate {
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        let mut edits = vec![];
+        for (idx, values) in occurrences(matches, "set") {
+            for (i, value) in values.into_iter().enumerate() {
+                let (path, value) = value.split_once('=').ok_or(anyhow!(
+                    r#"invalid "--set" value "{value}", expect "<path>=<value>""#
+                ))?;
+                edits.push((
+                    idx + i,
+                    Edit::Set {
+                        path: parse_path(path)?,
+                        value: value.to_string(),
+                    },
+                ));
+            }
+        }
+        for (idx, mut values) in occurrences(matches, "add") {
+            let path = parse_path(&values.remove(0))?;
+            if values.is_empty() {
+                bail!(r#""--add" requires the values to add after the path"#);
+            }
+            edits.push((idx, Edit::Add { path, values }));
+        }
+        for (idx, values) in occurrences(matches, "remove") {
+            let index = match values.get(1) {
+                Some(index) => Some(index.parse::<usize>().map_err(|_| {
+                    anyhow!(r#"invalid "--remove" index "{index}", expect a non-negative integer"#)
+                })?),
+                None => None,
+            };
+            if values.len() > 2 {
+                bail!(r#""--remove" accepts a path and an optional index only"#);
+            }
+            edits.push((
+                idx,
+                Edit::Remove {
+                    path: parse_path(&values[0])?,
+                    index,
+                },
+            ));
+        }
+        edits.sort_by_key(|(idx, _)| *idx);
+        let force_string = matches
+            .try_get_one::<bool>("force-string")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or_default();
+        Ok(Self {
+            edits,
+            force_string,
+        })
+    }
+
+    /// Validates the paths and values of the edits against the schema of the request body.
+    pub fn validate(&self, schema: &Schema, classes: &HashMap<String, Schema>) -> Result<()> {
+        for (_, edit) in &self.edits {
+            match edit {
+                Edit::Set { path, value } => {
+                    let target = schema_at(schema, path, classes)?;
+                    convert(value, Some(&target), classes, self.force_string)?;
+                }
+                Edit::Add { path, values } => {
+                    let target = schema_at(schema, path, classes)?;
+                    let item = list_item(&target, path, classes)?;
+                    for value in values {
+                        self.add_value(value, &item, classes)?;
+                    }
+                }
+                Edit::Remove { path, index } => {
+                    let target = schema_at(schema, path, classes)?;
+                    if index.is_some() {
+                        list_item(&target, path, classes)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the edits to the instance.
+    pub fn apply(
+        &self,
+        instance: &mut Value,
+        schema: &Schema,
+        classes: &HashMap<String, Schema>,
+    ) -> Result<()> {
+        self.validate(schema, classes)?;
+        for (_, edit) in &self.edits {
+            match edit {
+                Edit::Set { path, value } => {
+                    let target = schema_at(schema, path, classes)?;
+                    let value = convert(value, Some(&target), classes, self.force_string)?;
+                    let (last, parent) = path.split_last().unwrap();
+                    let parent = value_at(instance, parent, true)?;
+                    match (last, parent) {
+                        (Segment::Key(key), Value::Object(map)) => {
+                            map.insert(key.clone(), value);
+                        }
+                        (Segment::Index(i), Value::Array(arr)) if *i < arr.len() => {
+                            arr[*i] = value;
+                        }
+                        _ => bail!("couldn't find \"{}\" in the instance", display(path)),
+                    }
+                }
+                Edit::Add { path, values } => {
+                    let item = list_item(&schema_at(schema, path, classes)?, path, classes)?;
+                    let mut objects = serde_json::Map::new();
+                    let mut others = vec![];
+                    for value in values {
+                        match self.add_value(value, &item, classes)? {
+                            AddValue::Pair(k, v) => {
+                                objects.insert(k, v);
+                            }
+                            AddValue::Value(v) => others.push(v),
+                        }
+                    }
+                    let list = value_at(instance, path, true)?;
+                    if list.is_null() {
+                        *list = Value::Array(vec![]);
+                    }
+                    let Value::Array(arr) = list else {
+                        bail!("\"{}\" is not a list in the instance", display(path));
+                    };
+                    arr.extend(others);
+                    if !objects.is_empty() {
+                        arr.push(Value::Object(objects));
+                    }
+                }
+                Edit::Remove { path, index } => match index {
+                    Some(i) => {
+                        let Value::Array(arr) = value_at(instance, path, false)? else {
+                            bail!("\"{}\" is not a list in the instance", display(path));
+                        };
+                        if *i >= arr.len() {
+                            bail!(
+                                "index {i} is out of range for \"{}\" (length: {})",
+                                display(path),
+                                arr.len()
+                            );
+                        }
+                        arr.remove(*i);
+                    }
+                    None => {
+                        let (last, parent) = path.split_last().unwrap();
+                        match (last, value_at(instance, parent, false)?) {
+                            (Segment::Key(key), Value::Object(map)) => {
+                                map.remove(key);
+                            }
+                            (Segment::Index(i), Value::Array(arr)) if *i < arr.len() => {
+                                arr.remove(*i);
+                            }
+                            _ => bail!("couldn't find \"{}\" in the instance", display(path)),
+                        }
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn add_value(
+        &self,
+        value: &str,
+        item: &Schema,
+        classes: &HashMap<String, Schema>,
+    ) -> Result<AddValue> {
+        // The "key=value" form builds up an object to add, unless the value is a JSON object itself
+        if item.props.is_some()
+            && !value.trim_start().starts_with('{')
+            && let Some((key, value)) = value.split_once('=')
+        {
+            let prop = item
+                .prop(key)
+                .ok_or(anyhow!("property \"{key}\" is not found in the list item"))?;
+            return Ok(AddValue::Pair(
+                key.to_string(),
+                convert(value, Some(prop), classes, self.force_string)?,
+            ));
+        }
+        Ok(AddValue::Value(convert(
+            value,
+            Some(item),
+            classes,
+            self.force_string,
+        )?))
+    }
+}
+
+enum AddValue {
+    Pair(String, Value),
+    Value(Value),
+}
+
+// occurrences returns the values of each occurrence of the argument, together with the index of its first value.
+fn occurrences(matches: &ArgMatches, id: &str) -> Vec<(usize, Vec<String>)> {
+    let Ok(Some(occurrences)) = matches.try_get_occurrences::<String>(id) else {
+        return vec![];
+    };
+    let mut indices = matches.indices_of(id).into_iter().flatten();
+    occurrences
+        .map(|values| {
+            let values: Vec<String> = values.cloned().collect();
+            let idx = indices.next().unwrap_or_default();
+            values.iter().skip(1).for_each(|_| {
+                indices.next();
+            });
+            (idx, values)
+        })
+        .collect()
+}
+
+// parse_path parses a property path like "properties.subnets[0].name".
+fn parse_path(path: &str) -> Result<Vec<Segment>> {
+    let mut segments = vec![];
+    for part in path.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(i) => part.split_at(i),
+            None => (part, ""),
+        };
+        if key.is_empty() && (segments.is_empty() || rest.is_empty()) {
+            bail!("invalid property path \"{path}\"");
+        }
+        if !key.is_empty() {
+            segments.push(Segment::Key(key.to_string()));
+        }
+        while !rest.is_empty() {
+            let end = rest
+                .find(']')
+                .ok_or(anyhow!("invalid property path \"{path}\""))?;
+            let index = rest[1..end]
+                .parse::<usize>()
+                .map_err(|_| anyhow!("invalid list index in property path \"{path}\""))?;
+            segments.push(Segment::Index(index));
+            rest = &rest[end + 1..];
+            if !rest.is_empty() && !rest.starts_with('[') {
+                bail!("invalid property path \"{path}\"");
+            }
+        }
+    }
+    Ok(segments)
+}
+
+fn display(path: &[Segment]) -> String {
+    let mut out = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key) => {
+                if !out.is_empty() {
+                    out.push('.');
+                }
+                out.push_str(key);
+            }
+            Segment::Index(i) => out.push_str(&format!("[{i}]")),
+        }
+    }
+    out
+}
+
+// schema_at returns the schema of the property at the path.
+fn schema_at(
+    schema: &Schema,
+    path: &[Segment],
+    classes: &HashMap<String, Schema>,
+) -> Result<Schema> {
+    let mut schema = schema.resolve(classes);
+    for (i, segment) in path.iter().enumerate() {
+        let next = match segment {
+            Segment::Key(key) => schema.prop(key).cloned().or_else(|| {
+                schema.additional_props.as_ref().map(|ap| Schema {
+                    type_: ap.item.type_.clone(),
+                    ..Default::default()
+                })
+            }),
+            Segment::Index(_) => schema.item.as_deref().cloned(),
+        };
+        schema = next
+            .ok_or(anyhow!(
+                "\"{}\" is not a valid property path of the resource",
+                display(&path[..=i])
+            ))?
+            .resolve(classes);
+    }
+    Ok(schema)
+}
+
+fn list_item(
+    schema: &Schema,
+    path: &[Segment],
+    classes: &HashMap<String, Schema>,
+) -> Result<Schema> {
+    match &schema.item {
+        Some(item) if schema.type_.starts_with("array") => Ok(item.resolve(classes)),
+        _ => bail!("\"{}\" is not a list", display(path)),
+    }
+}
+
+// value_at returns the value at the path of the instance.
+// When create is true, the missing objects along the path are created.
+fn value_at<'a>(instance: &'a mut Value, path: &[Segment], create: bool) -> Result<&'a mut Value> {
+    let mut value = instance;
+    for (i, segment) in path.iter().enumerate() {
+        value = match segment {
+            Segment::Key(key) => {
+                if create && value.is_null() {
+                    *value = Value::Object(serde_json::Map::new());
+                }
+                let Value::Object(map) = value else {
+                    bail!("\"{}\" is not an object", display(&path[..i]));
+                };
+                if create {
+                    map.entry(key.clone()).or_insert(Value::Null)
+                } else {
+                    map.get_mut(key).ok_or(anyhow!(
+                        "couldn't find \"{}\" in the instance",
+                        display(&path[..=i])
+                    ))?
+                }
+            }
+            Segment::Index(idx) => match value {
+                Value::Array(arr) => {
+                    let len = arr.len();
+                    arr.get_mut(*idx).ok_or(anyhow!(
+                        "index {idx} is out of range for \"{}\" (length: {len})",
+                        display(&path[..i])
+                    ))?
+                }
+                _ => bail!("\"{}\" is not a list", display(&path[..i])),
+            },
+        };
+    }
+    Ok(value)
+}
+
+// convert converts the raw string value to JSON, according to the type of the target schema.
+fn convert(
+    value: &str,
+    schema: Option<&Schema>,
+    classes: &HashMap<String, Schema>,
+    force_string: bool,
+) -> Result<Value> {
+    if force_string {
+        return Ok(Value::String(value.to_string()));
+    }
+    if value == "null" {
+        return Ok(Value::Null);
+    }
+    let Some(schema) = schema else {
+        return Ok(serde_json::from_str(value).unwrap_or(Value::String(value.to_string())));
+    };
+    let type_ = schema.type_.as_str();
+    let invalid = || anyhow!("invalid value \"{value}\", expect type \"{type_}\"");
+    match ArgType::from(type_) {
+        // The objects and arrays are in the shorthand syntax (or JSON), whose scalars are converted by the schema
+        ArgType::Object | ArgType::Array(_) => shorthand::parse(value)
+            .and_then(|v| shorthand::conform(v, schema, classes))
+            .map_err(|e| anyhow!("{}: {e}", invalid())),
+        ArgType::Other => Ok(Value::String(value.to_string())),
+        arg_type => arg_type
+            .normalize(value)
+            .and_then(|v| arg_type.to_json(&[v]))
+            .map_err(|_| invalid()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::{Arg, ArgAction, Command};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn update(args: &[&str]) -> Result<GenericUpdate> {
+        let cmd = Command::new("update").args([
+            Arg::new("set")
+                .long("set")
+                .num_args(1..)
+                .action(ArgAction::Append),
+            Arg::new("add")
+                .long("add")
+                .num_args(1..)
+                .action(ArgAction::Append),
+            Arg::new("remove")
+                .long("remove")
+                .num_args(1..)
+                .action(ArgAction::Append),
+            Arg::new("force-string")
+                .long("force-string")
+                .action(ArgAction::SetTrue),
+        ]);
+        GenericUpdate::from_matches(&cmd.get_matches_from([&["update"], args].concat()))
+    }
+
+    fn schema() -> Schema {
+        serde_json::from_value(json!({
+            "type": "object",
+            "props": [
+                {"type": "ResourceLocation", "name": "location"},
+                {"type": "object", "name": "tags", "additionalProps": {"item": {"type": "string"}}},
+                {"type": "object", "name": "properties", "props": [
+                    {"type": "integer32", "name": "count"},
+                    {"type": "array<@Rule>", "name": "rules", "item": {"type": "@Rule"}}
+                ]}
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn classes() -> HashMap<String, Schema> {
+        let rule = serde_json::from_value(json!({
+            "type": "object",
+            "cls": "Rule",
+            "props": [{"type": "string", "name": "name"}, {"type": "integer32", "name": "priority"}]
+        }))
+        .unwrap();
+        HashMap::from([("Rule".to_string(), rule)])
+    }
+
+    #[test]
+    fn parse_path() {
+        assert_eq!(
+            super::parse_path("a.b[0][1].c").unwrap(),
+            vec![
+                Segment::Key("a".to_string()),
+                Segment::Key("b".to_string()),
+                Segment::Index(0),
+                Segment::Index(1),
+                Segment::Key("c".to_string()),
+            ]
+        );
+        assert!(super::parse_path("a..b").is_err());
+        assert!(super::parse_path("a[x]").is_err());
+        assert!(super::parse_path("a[0").is_err());
+    }
+
+    #[test]
+    fn apply() {
+        let mut instance = json!({
+            "location": "eastus",
+            "tags": {"env": "dev"},
+            "properties": {"count": 1, "rules": [{"name": "a", "priority": 1}, {"name": "b", "priority": 2}]}
+        });
+        update(&[
+            "--set",
+            "tags.version=2",
+            "properties.count=3",
+            "--add",
+            "properties.rules",
+            "name=c",
+            "priority=3",
+            "--remove",
+            "properties.rules",
+            "0",
+            "--remove",
+            "tags.env",
+        ])
+        .unwrap()
+        .apply(&mut instance, &schema(), &classes())
+        .unwrap();
+        assert_eq!(
+            instance,
+            json!({
+                "location": "eastus",
+                "tags": {"version": "2"},
+                "properties": {"count": 3, "rules": [{"name": "b", "priority": 2}, {"name": "c", "priority": 3}]}
+            })
+        );
+    }
+
+    #[test]
+    fn validate() {
+        let update_err = |args: &[&str]| {
+            update(args)
+                .and_then(|u| u.validate(&schema(), &classes()))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            update_err(&["--set", "properties.cnt=3"]),
+            r#""properties.cnt" is not a valid property path of the resource"#
+        );
+        assert_eq!(
+            update_err(&["--set", "properties.count=abc"]),
+            r#"invalid value "abc", expect type "integer32""#
+        );
+        assert_eq!(
+            update_err(&["--add", "tags", "a=b"]),
+            r#""tags" is not a list"#
+        );
+        assert_eq!(
+            update_err(&["--add", "properties.rules", "foo=bar"]),
+            r#"property "foo" is not found in the list item"#
+        );
+        assert!(update(&["--set", "properties.count=abc", "--force-string"])
+            .unwrap()
+            .validate(&schema(), &classes())
+            .is_ok());
+    }
+}
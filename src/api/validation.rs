@@ -0,0 +1,500 @@
+use std::collections::{HashMap, HashSet};
+
+use clap::ArgMatches;
+use regex::Regex;
+use serde_json::Value;
+
+use super::metadata::{Arg, Command, Enum, RequestFormat, Schema};
+use super::shorthand;
+use super::types::ArgType;
+use crate::error::{InvalidArguments, Violation};
+
+// validate checks the arguments against the constraints declared by the metadata, i.e. the types of the arguments,
+// the formats of the arguments and of the request parameters bound to them, the allowed values, and the constraints
+// of the body properties nested in the arguments. All the violations are reported at once.
+pub(crate) fn validate(command: &Command, matches: &ArgMatches) -> Result<(), InvalidArguments> {
+    let param_formats = param_formats(command);
+    let mut violations = Violations::default();
+    let mut invalid = HashSet::new();
+    for arg in command.arg_groups.iter().flat_map(|ag| &ag.args) {
+        let Some(values) = matches.try_get_many::<String>(&arg.var).ok().flatten() else {
+            continue;
+        };
+        let formats: Vec<&RequestFormat> = arg
+            .format
+            .iter()
+            .chain(
+                param_formats
+                    .get(arg.var.as_str())
+                    .into_iter()
+                    .flatten()
+                    .copied(),
+            )
+            .collect();
+        let type_ = ArgType::from(arg.type_.as_str());
+        let option = arg.option_name();
+        for value in values {
+            // The parser keeps the value of an invalid type, so that it is reported along with the others
+            if let Err(e) = type_.normalize(value) {
+                violations.push(&option, e.to_string());
+                invalid.insert(arg.var.as_str());
+                continue;
+            }
+            for value in items(&arg.type_, value) {
+                let messages = formats
+                    .iter()
+                    .flat_map(|format| check_format(format, &value))
+                    .chain(arg.enum_.as_ref().and_then(|e| check_enum(e, &value)));
+                for message in messages {
+                    violations.push(&option, message);
+                }
+            }
+        }
+    }
+
+    let classes = command.schema_classes();
+    for (arg, schema) in body_schemas(command) {
+        if invalid.contains(arg.var.as_str()) {
+            continue;
+        }
+        let Some(values) = matches.try_get_many::<String>(&arg.var).ok().flatten() else {
+            continue;
+        };
+        let values: Vec<String> = values.cloned().collect();
+        let option = arg.option_name();
+        let value = match ArgType::from(schema.type_.as_str())
+            .to_json(&values)
+            .and_then(|value| shorthand::conform(value, schema, &classes))
+        {
+            Ok(value) => value,
+            Err(e) => {
+                violations.push(
+                    &option,
+                    format!(r#"invalid value "{}": {e}"#, values.join(" ")),
+                );
+                continue;
+            }
+        };
+        // The value of the argument itself is checked above, so only the nested properties are checked here
+        let mut messages = vec![];
+        check_props(&value, schema, &classes, "", &mut messages);
+        for message in messages {
+            violations.push(&option, message);
+        }
+    }
+    violations.into_result()
+}
+
+#[derive(Default)]
+struct Violations(Vec<Violation>);
+
+impl Violations {
+    fn push(&mut self, option: &str, message: String) {
+        let violation = Violation {
+            option: option.to_string(),
+            message,
+        };
+        // The same parameter can be bound by multiple operations, e.g. the GET and PUT of an update
+        if !self.0.contains(&violation) {
+            self.0.push(violation);
+        }
+    }
+
+    fn into_result(self) -> Result<(), InvalidArguments> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        Err(InvalidArguments { violations: self.0 })
+    }
+}
+
+// body_schemas returns the schemas of the request bodies and of the instance updates that are bound to the
+// arguments, paired with the argument.
+fn body_schemas(command: &Command) -> Vec<(&Arg, &Schema)> {
+    fn collect<'a>(schema: &'a Schema, schemas: &mut Vec<&'a Schema>) {
+        if schema.arg.is_some() {
+            schemas.push(schema);
+            return;
+        }
+        schema
+            .props
+            .iter()
+            .flatten()
+            .for_each(|prop| collect(prop, schemas));
+    }
+
+    let mut schemas = vec![];
+    for op in &command.operations {
+        let bodies = op
+            .http
+            .iter()
+            .filter_map(|http| http.request.body.as_ref())
+            .map(|body| &body.json)
+            .chain(op.instance_update.iter().map(|update| &update.json));
+        for schema in bodies.filter_map(|json| json.schema.as_ref()) {
+            collect(schema, &mut schemas);
+        }
+    }
+    let args: HashMap<&str, &Arg> = command
+        .arg_groups
+        .iter()
+        .flat_map(|ag| &ag.args)
+        .map(|arg| (arg.var.as_str(), arg))
+        .collect();
+    schemas
+        .into_iter()
+        .filter_map(|schema| Some((*args.get(schema.arg.as_deref()?)?, schema)))
+        .collect()
+}
+
+// check_props checks the properties and the items nested in the value against the formats and the allowed values
+// of their schemas. The messages are prefixed with the path of the property, e.g. "[0].properties.protocol".
+fn check_props(
+    value: &Value,
+    schema: &Schema,
+    classes: &HashMap<String, Schema>,
+    path: &str,
+    messages: &mut Vec<String>,
+) {
+    let schema = schema.resolve(classes);
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let Some(prop) = schema
+                    .props
+                    .iter()
+                    .flatten()
+                    .find(|prop| prop.name.as_deref() == Some(key))
+                else {
+                    continue;
+                };
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                check_value(value, prop, classes, &path, messages);
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item) = &schema.item {
+                for (i, value) in items.iter().enumerate() {
+                    check_value(value, item, classes, &format!("{path}[{i}]"), messages);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_value(
+    value: &Value,
+    schema: &Schema,
+    classes: &HashMap<String, Schema>,
+    path: &str,
+    messages: &mut Vec<String>,
+) {
+    if value.is_object() || value.is_array() {
+        check_props(value, schema, classes, path, messages);
+        return;
+    }
+    if value.is_null() {
+        return;
+    }
+    let schema = schema.resolve(classes);
+    let value = value_string(value);
+    let format = schema.format.as_ref().map(|format| &format.constraints);
+    messages.extend(
+        format
+            .into_iter()
+            .flat_map(|format| check_format(format, &value))
+            .chain(schema.enum_.as_ref().and_then(|e| check_enum(e, &value)))
+            .map(|message| format!("{path}: {message}")),
+    );
+}
+
+// param_formats returns the formats of the path, query and header parameters of the operations, keyed by the
+// argument they are bound to.
+fn param_formats(command: &Command) -> HashMap<&str, Vec<&RequestFormat>> {
+    let mut formats: HashMap<&str, Vec<&RequestFormat>> = HashMap::new();
+    for request in command
+        .operations
+        .iter()
+        .filter_map(|op| op.http.as_ref())
+        .map(|http| &http.request)
+    {
+        let params = request
+            .path
+            .params
+            .iter()
+            .map(|p| (&p.arg, &p.format))
+            .chain(
+                request
+                    .query
+                    .params
+                    .iter()
+                    .flatten()
+                    .map(|p| (&p.arg, &p.format)),
+            )
+            .chain(
+                request
+                    .header
+                    .iter()
+                    .flat_map(|h| h.params.iter().flatten())
+                    .map(|p| (&p.arg, &p.format)),
+            );
+        for (arg, format) in params {
+            if let Some(format) = format {
+                formats.entry(arg.as_str()).or_default().push(format);
+            }
+        }
+    }
+    formats
+}
+
+// items returns the values to check for the argument value, which are the items of an array given as a whole,
+// e.g. "[a,b]".
+fn items(type_: &str, value: &str) -> Vec<String> {
+    if ArgType::from(type_).is_array()
+        && value.trim_start().starts_with('[')
+        && let Ok(Value::Array(items)) = shorthand::parse(value)
+    {
+        return items.iter().map(value_string).collect();
+    }
+    vec![value.to_string()]
+}
+
+fn value_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+fn check_format(format: &RequestFormat, value: &str) -> Vec<String> {
+    let mut messages = vec![];
+    // A pattern that can't be compiled is not the fault of the user, which is skipped
+    if let Some(pattern) = &format.pattern
+        && let Ok(re) = Regex::new(pattern)
+        && !re.is_match(value)
+    {
+        messages.push(format!(
+            r#""{value}" doesn't match the pattern "{pattern}""#
+        ));
+    }
+    let len = value.chars().count() as i64;
+    if let Some(min_length) = format.min_length
+        && len < min_length
+    {
+        messages.push(format!(
+            r#""{value}" is shorter than the minimum length {min_length}"#
+        ));
+    }
+    if let Some(max_length) = format.max_length
+        && len > max_length
+    {
+        messages.push(format!(
+            r#""{value}" is longer than the maximum length {max_length}"#
+        ));
+    }
+    if let Ok(number) = value.parse::<f64>() {
+        if let Some(minimum) = format.minimum
+            && number < minimum
+        {
+            messages.push(format!("{value} is less than the minimum {minimum}"));
+        }
+        if let Some(maximum) = format.maximum
+            && number > maximum
+        {
+            messages.push(format!("{value} is greater than the maximum {maximum}"));
+        }
+    }
+    messages
+}
+
+// check_enum checks the value is one of the allowed values, which is case insensitive as ARM is.
+fn check_enum(enum_: &Enum, value: &str) -> Option<String> {
+    let allowed: Vec<String> = enum_
+        .items
+        .iter()
+        .map(|item| value_string(&item.value))
+        .collect();
+    if allowed.iter().any(|v| v.eq_ignore_ascii_case(value)) {
+        return None;
+    }
+    Some(format!(
+        r#""{value}" is not one of the allowed values: {}"#,
+        allowed.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::Command as ClapCommand;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn validate_arguments() {
+        let command: Command = serde_json::from_value(json!({
+            "name": "create",
+            "version": "2024-07-01",
+            "resources": [],
+            "argGroups": [{
+                "name": "",
+                "args": [
+                    {"type": "string", "var": "$Path.resourceGroupName", "options": ["g", "resource-group"]},
+                    {"type": "integer32", "var": "$foo.timeout", "options": ["timeout"], "format": {"minimum": 4, "maximum": 30}},
+                    {"type": "array<string>", "var": "$foo.tiers", "options": ["tiers"], "enum": {"items": [
+                        {"name": "Basic", "value": "Basic"},
+                        {"name": "Premium", "value": "Premium"}
+                    ]}}
+                ]
+            }],
+            "operations": [{
+                "http": {
+                    "path": "/subscriptions/{subscriptionId}/resourcegroups/{resourceGroupName}",
+                    "request": {
+                        "method": "put",
+                        "path": {"params": [{
+                            "type": "string",
+                            "name": "resourceGroupName",
+                            "arg": "$Path.resourceGroupName",
+                            "format": {"pattern": "^[-\\w\\._\\(\\)]+$", "maxLength": 10, "minLength": 1}
+                        }]},
+                        "query": {"consts": []}
+                    },
+                    "responses": []
+                }
+            }]
+        }))
+        .unwrap();
+        let cmd = ClapCommand::new("create").args([
+            clap::Arg::new("$Path.resourceGroupName")
+                .short('g')
+                .long("resource-group"),
+            clap::Arg::new("$foo.timeout").long("timeout"),
+            clap::Arg::new("$foo.tiers")
+                .long("tiers")
+                .num_args(1..)
+                .action(clap::ArgAction::Append),
+        ]);
+
+        let matches = cmd.clone().get_matches_from([
+            "create",
+            "-g",
+            "foo",
+            "--timeout",
+            "4",
+            "--tiers",
+            "basic",
+            "Premium",
+        ]);
+        assert_eq!(validate(&command, &matches), Ok(()));
+
+        let matches = cmd.clone().get_matches_from([
+            "create",
+            "-g",
+            "foo#bar-baz",
+            "--timeout",
+            "31",
+            "--tiers",
+            "[Basic, Free]",
+        ]);
+        assert_eq!(
+            validate(&command, &matches).unwrap_err().to_string(),
+            r#"invalid arguments:
+  --resource-group: "foo#bar-baz" doesn't match the pattern "^[-\w\._\(\)]+$"
+  --resource-group: "foo#bar-baz" is longer than the maximum length 10
+  --timeout: 31 is greater than the maximum 30
+  --tiers: "Free" is not one of the allowed values: Basic, Premium"#
+        );
+
+        // The invalid typed value is reported along with the other violations
+        let matches = cmd.get_matches_from(["create", "-g", "foo#bar", "--timeout", "abc"]);
+        assert_eq!(
+            validate(&command, &matches).unwrap_err().to_string(),
+            r#"invalid arguments:
+  --resource-group: "foo#bar" doesn't match the pattern "^[-\w\._\(\)]+$"
+  --timeout: invalid value "abc", expect a 32-bit integer"#
+        );
+    }
+
+    #[test]
+    fn validate_body_properties() {
+        let rule = json!({
+            "type": "object",
+            "cls": "Rule",
+            "props": [
+                {"type": "object", "name": "properties", "clientFlatten": true, "props": [
+                    {"type": "string", "name": "protocol", "enum": {"items": [
+                        {"name": "Tcp", "value": "Tcp"},
+                        {"name": "Udp", "value": "Udp"}
+                    ]}},
+                    {"type": "integer32", "name": "port", "format": {"minimum": 1, "maximum": 65535}},
+                    {"type": "string", "name": "name", "format": {"maxLength": 5}}
+                ]}
+            ]
+        });
+        let command: Command = serde_json::from_value(json!({
+            "name": "create",
+            "version": "2024-07-01",
+            "resources": [],
+            "argGroups": [{
+                "name": "",
+                "args": [
+                    {"type": "array<@Rule>", "var": "$rules", "options": ["rules"]}
+                ]
+            }],
+            "operations": [{
+                "http": {
+                    "path": "/subscriptions/{subscriptionId}/providers/Foo/bars/{name}",
+                    "request": {
+                        "method": "put",
+                        "path": {"params": []},
+                        "query": {"consts": []},
+                        "body": {"json": {"schema": {"type": "object", "props": [
+                            {"type": "array<@Rule>", "name": "rules", "arg": "$rules", "item": rule}
+                        ]}}}
+                    },
+                    "responses": []
+                }
+            }]
+        }))
+        .unwrap();
+        let cmd = ClapCommand::new("create").arg(
+            clap::Arg::new("$rules")
+                .long("rules")
+                .num_args(1..)
+                .action(clap::ArgAction::Append),
+        );
+
+        let matches =
+            cmd.clone()
+                .get_matches_from(["create", "--rules", "{protocol:udp,port:80,name:http}"]);
+        assert_eq!(validate(&command, &matches), Ok(()));
+
+        let matches = cmd.clone().get_matches_from([
+            "create",
+            "--rules",
+            "{protocol:Icmp,port:0}",
+            "{port:70000,name:toolong}",
+        ]);
+        assert_eq!(
+            validate(&command, &matches).unwrap_err().to_string(),
+            r#"invalid arguments:
+  --rules: [0].properties.port: 0 is less than the minimum 1
+  --rules: [0].properties.protocol: "Icmp" is not one of the allowed values: Tcp, Udp
+  --rules: [1].properties.name: "toolong" is longer than the maximum length 5
+  --rules: [1].properties.port: 70000 is greater than the maximum 65535"#
+        );
+
+        let matches = cmd.get_matches_from(["create", "--rules", "{port:abc}"]);
+        assert_eq!(
+            validate(&command, &matches).unwrap_err().to_string(),
+            r#"invalid arguments:
+  --rules: invalid value "{port:abc}": invalid value "abc", expect a 32-bit integer"#
+        );
+    }
+}
@@ -0,0 +1,811 @@
+use anyhow::{anyhow, Result};
+use azure::api::metadata::CommandGroup;
+use azure::api::ApiManager;
+use azure_core::http::Method;
+use clap::{value_parser, Arg, Command};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+// MOCK_ID is the id of the emulated subscription and its tenant.
+const MOCK_ID: &str = "00000000-0000-0000-0000-000000000000";
+
+// azure-mock emulates the ARM endpoints of the RPs in the metadata directory, which keeps the resources in memory.
+// Point the CLI at it via `AZURE_RS_ENDPOINT=http://127.0.0.1:<port>` to run the commands without network.
+fn main() -> Result<()> {
+    let matches = Command::new("azure-mock")
+        .about("Emulate the ARM endpoints of the RPs in the metadata directory.")
+        .args([
+            Arg::new("metadata")
+                .long("metadata")
+                .value_name("dir")
+                .default_value("./metadata")
+                .help("The directory of the API metadata."),
+            Arg::new("port")
+                .long("port")
+                .value_name("port")
+                .value_parser(value_parser!(u16))
+                .default_value("8080")
+                .help("The port to listen on, 0 picks a free one."),
+            Arg::new("polls")
+                .long("polls")
+                .value_name("count")
+                .value_parser(value_parser!(u32))
+                .default_value("2")
+                .help("The number of polls before a long running operation completes."),
+            Arg::new("retry-after")
+                .long("retry-after")
+                .value_name("seconds")
+                .value_parser(value_parser!(u64))
+                .default_value("1")
+                .help("The Retry-After of the in-progress long running operations."),
+        ])
+        .get_matches();
+
+    let api_manager = ApiManager::new(PathBuf::from_str(
+        matches.get_one::<String>("metadata").unwrap(),
+    )?)?;
+    let mut routes = vec![];
+    for rp in api_manager.list_rps() {
+        for group in api_manager.read_metadata(rp)?.command_groups {
+            collect_routes(&group, &mut routes);
+        }
+    }
+    let mut emulator = Emulator::new(
+        routes,
+        *matches.get_one::<u32>("polls").unwrap(),
+        *matches.get_one::<u64>("retry-after").unwrap(),
+    );
+
+    let port = *matches.get_one::<u16>("port").unwrap();
+    let server = tiny_http::Server::http(("127.0.0.1", port)).map_err(|e| anyhow!("{e}"))?;
+    let addr = server
+        .server_addr()
+        .to_ip()
+        .ok_or(anyhow!("unexpected listening address"))?;
+    println!("Listening on http://{addr}");
+    let base = format!("http://{addr}");
+    for mut request in server.incoming_requests() {
+        // A bad request is answered with an error, which doesn't stop the emulator
+        let mut body = String::new();
+        let resp = match (
+            request.as_reader().read_to_string(&mut body),
+            Method::from_str(request.method().as_str()),
+        ) {
+            (Err(e), _) => MockResponse::error(
+                400,
+                "InvalidRequestContent",
+                format!("reading the request body: {e}"),
+            ),
+            (_, Err(_)) => MockResponse::error(
+                405,
+                "MethodNotAllowed",
+                format!("the method {} is not supported", request.method()),
+            ),
+            (Ok(_), Ok(method)) => emulator.handle(method, request.url(), &body, &base),
+        };
+        eprintln!("{} {} {}", request.method(), request.url(), resp.status);
+
+        let mut response =
+            tiny_http::Response::from_string(resp.body.map(|b| b.to_string()).unwrap_or_default())
+                .with_status_code(resp.status);
+        for (name, value) in resp
+            .headers
+            .into_iter()
+            .chain([("Content-Type".to_string(), "application/json".to_string())])
+        {
+            if let Ok(header) = tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+                response.add_header(header);
+            }
+        }
+        // The client may have gone, e.g. the command is interrupted
+        if let Err(e) = request.respond(response) {
+            eprintln!("responding to the request: {e}");
+        }
+    }
+    Ok(())
+}
+
+fn collect_routes(group: &CommandGroup, routes: &mut Vec<Route>) {
+    for command in &group.commands {
+        for operation in &command.operations {
+            let Some(http) = &operation.http else {
+                continue;
+            };
+            routes.push(Route {
+                method: http.request.method.into(),
+                template: segments(&http.path),
+                long_running: operation.long_running.as_ref().map(|lro| {
+                    lro.final_state_via
+                        .clone()
+                        .unwrap_or("original-uri".to_string())
+                }),
+                pageable: operation.pageable.is_some(),
+            });
+        }
+    }
+    for group in group.command_groups.iter().flatten() {
+        collect_routes(group, routes);
+    }
+}
+
+// Route is an operation declared by the metadata.
+#[derive(Debug, Clone)]
+struct Route {
+    method: Method,
+    // The path template split by "/", e.g. ["subscriptions", "{subscriptionId}"].
+    template: Vec<String>,
+    // The final-state-via of the LRO, which decides the polling protocol. None means it is not a LRO.
+    long_running: Option<String>,
+    pageable: bool,
+}
+
+impl Route {
+    fn matches(&self, method: Method, path: &[String]) -> bool {
+        self.method == method
+            && self.template.len() == path.len()
+            && self
+                .template
+                .iter()
+                .zip(path)
+                .all(|(t, p)| (t.starts_with('{') && t.ends_with('}')) || t.eq_ignore_ascii_case(p))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Option<Value>,
+}
+
+impl MockResponse {
+    fn new(status: u16, body: Option<Value>) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body,
+        }
+    }
+
+    fn error(status: u16, code: &str, message: String) -> Self {
+        Self::new(
+            status,
+            Some(json!({"error": {"code": code, "message": message}})),
+        )
+    }
+
+    fn with_header(mut self, name: &str, value: String) -> Self {
+        self.headers.push((name.to_string(), value));
+        self
+    }
+}
+
+// Polling is the protocol to poll a LRO, which covers every handler of the poller.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Polling {
+    // Azure-AsyncOperation header, with the Location header for DELETE and POST.
+    AsyncOp,
+    // Operation-Location header.
+    Op,
+    // Location header.
+    Loc,
+    // No header, the resource itself is polled for its provisioning state.
+    Body,
+}
+
+// Outcome is what happens to the resource once the LRO succeeds.
+#[derive(Debug, Clone, PartialEq)]
+enum Outcome {
+    Provisioned,
+    Deleted,
+    Action,
+}
+
+#[derive(Debug, Clone)]
+struct Operation {
+    // The lowercased id of the resource.
+    key: String,
+    polling: Polling,
+    outcome: Outcome,
+    remaining_polls: u32,
+    start_time: String,
+}
+
+impl Operation {
+    fn done(&self) -> bool {
+        self.remaining_polls == 0
+    }
+}
+
+// Emulator keeps the resources in memory keyed by the lowercased resource id, and drives the LROs which
+// complete after a number of polls.
+struct Emulator {
+    routes: Vec<Route>,
+    polls: u32,
+    retry_after: u64,
+    resources: BTreeMap<String, Value>,
+    operations: BTreeMap<String, Operation>,
+    next_operation: u64,
+}
+
+impl Emulator {
+    fn new(routes: Vec<Route>, polls: u32, retry_after: u64) -> Self {
+        Self {
+            routes,
+            polls,
+            retry_after,
+            resources: BTreeMap::new(),
+            operations: BTreeMap::new(),
+            next_operation: 0,
+        }
+    }
+
+    // handle serves the request, the base is the URL of the emulator, which prefixes the polling URLs.
+    fn handle(&mut self, method: Method, url: &str, body: &str, base: &str) -> MockResponse {
+        let path = url.split('?').next().unwrap_or_default();
+        let segs = segments(path);
+
+        // The polling endpoints of the LROs
+        if let [_, _, _, provider, kind, id] = segs.as_slice()
+            && provider.eq_ignore_ascii_case("Microsoft.Mock")
+            && method == Method::Get
+        {
+            return match kind.as_str() {
+                "operations" => self.operation_status(id),
+                "operationResults" => self.operation_result(id, base),
+                _ => not_found(path),
+            };
+        }
+
+        // The subscriptions are not emulated, there is a fixed one for "account set"
+        if segs == ["subscriptions"] && method == Method::Get {
+            return MockResponse::new(
+                200,
+                Some(json!({"value": [{
+                    "id": format!("/subscriptions/{MOCK_ID}"),
+                    "subscriptionId": MOCK_ID,
+                    "tenantId": MOCK_ID,
+                    "displayName": "azure-mock",
+                    "state": "Enabled",
+                }]})),
+            );
+        }
+
+        let Some(route) = self
+            .routes
+            .iter()
+            .find(|r| r.matches(method, &segs))
+            .cloned()
+        else {
+            return MockResponse::error(
+                404,
+                "InvalidResourceType",
+                format!("no route for {method} {path}"),
+            );
+        };
+        let body = match body.trim() {
+            "" => None,
+            body => match serde_json::from_str::<Value>(body) {
+                Ok(body) => Some(body),
+                Err(e) => {
+                    return MockResponse::error(400, "InvalidRequestContent", e.to_string());
+                }
+            },
+        };
+        let polling = route.long_running.as_deref().map(|via| match via {
+            "azure-async-operation" => Polling::AsyncOp,
+            "operation-location" => Polling::Op,
+            "location" => Polling::Loc,
+            _ if matches!(method, Method::Put | Method::Patch) => Polling::Body,
+            _ => Polling::Loc,
+        });
+        let key = path.to_lowercase();
+        match method {
+            Method::Get if route.pageable => self.list(&key),
+            Method::Get => self.get(&key, path),
+            Method::Head => match self.resources.contains_key(&key) {
+                true => MockResponse::new(204, None),
+                false => MockResponse::new(404, None),
+            },
+            Method::Put | Method::Patch => self.put(method, path, body, polling, base),
+            Method::Delete => self.delete(path, polling, base),
+            _ => self.action(path, polling, base),
+        }
+    }
+
+    fn get(&mut self, key: &str, path: &str) -> MockResponse {
+        // The resource is polled for its provisioning state
+        let pending = self
+            .operations
+            .iter()
+            .find(|(_, op)| op.key == key && op.polling == Polling::Body && !op.done())
+            .map(|(id, _)| id.clone());
+        if let Some(id) = pending {
+            self.advance(&id);
+        }
+        match self.resources.get(key) {
+            Some(resource) => MockResponse::new(200, Some(resource.clone())),
+            None => not_found(path),
+        }
+    }
+
+    fn list(&self, key: &str) -> MockResponse {
+        let depth = key.split('/').count() + 1;
+        let items: Vec<Value> = self
+            .resources
+            .iter()
+            .filter(|(k, _)| k.starts_with(&format!("{key}/")) && k.split('/').count() == depth)
+            .map(|(_, v)| v.clone())
+            .collect();
+        MockResponse::new(200, Some(json!({"value": items})))
+    }
+
+    fn put(
+        &mut self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+        polling: Option<Polling>,
+        base: &str,
+    ) -> MockResponse {
+        let key = path.to_lowercase();
+        if let Some(parent) = parent(path)
+            && !self.resources.contains_key(&parent.to_lowercase())
+        {
+            return not_found(&parent);
+        }
+        let existing = self.resources.get(&key).cloned();
+        let mut resource = match (method, existing.clone()) {
+            (Method::Patch, None) => return not_found(path),
+            (Method::Patch, Some(mut existing)) => {
+                merge(&mut existing, body.unwrap_or(json!({})));
+                existing
+            }
+            (_, _) => body.unwrap_or(json!({})),
+        };
+        let Some(obj) = resource.as_object_mut() else {
+            return MockResponse::error(
+                400,
+                "InvalidRequestContent",
+                "the resource must be an object".to_string(),
+            );
+        };
+        obj.insert("id".to_string(), json!(path));
+        obj.insert("name".to_string(), json!(path.rsplit('/').next()));
+        obj.insert("type".to_string(), json!(resource_type(path)));
+        let state = match polling {
+            Some(_) if existing.is_some() => "Updating",
+            Some(_) => "Creating",
+            None => "Succeeded",
+        };
+        let properties = obj
+            .entry("properties")
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Some(properties) = properties.as_object_mut() {
+            properties.insert("provisioningState".to_string(), json!(state));
+        }
+        self.resources.insert(key.clone(), resource.clone());
+
+        let status = if existing.is_some() { 200 } else { 201 };
+        let Some(polling) = polling else {
+            return MockResponse::new(status, Some(resource));
+        };
+        let id = self.start(key, polling, Outcome::Provisioned);
+        let resp = MockResponse::new(status, Some(resource));
+        match polling {
+            Polling::AsyncOp => {
+                resp.with_header("Azure-AsyncOperation", operation_url(base, path, &id))
+            }
+            Polling::Op => resp.with_header("Operation-Location", operation_url(base, path, &id)),
+            Polling::Loc => MockResponse::new(202, None)
+                .with_header("Location", result_url(base, path, &id))
+                .with_header("Retry-After", self.retry_after.to_string()),
+            Polling::Body => resp,
+        }
+    }
+
+    fn delete(&mut self, path: &str, polling: Option<Polling>, base: &str) -> MockResponse {
+        let key = path.to_lowercase();
+        if !self.resources.contains_key(&key) {
+            return MockResponse::new(204, None);
+        }
+        let Some(polling) = polling else {
+            self.remove(&key);
+            return MockResponse::new(200, None);
+        };
+        if let Some(properties) = self
+            .resources
+            .get_mut(&key)
+            .and_then(|r| r.get_mut("properties"))
+            .and_then(Value::as_object_mut)
+        {
+            properties.insert("provisioningState".to_string(), json!("Deleting"));
+        }
+        let id = self.start(key, polling, Outcome::Deleted);
+        self.accepted(path, polling, &id, base)
+    }
+
+    fn action(&mut self, path: &str, polling: Option<Polling>, base: &str) -> MockResponse {
+        let Some(polling) = polling else {
+            return MockResponse::new(200, Some(json!({})));
+        };
+        let id = self.start(path.to_lowercase(), polling, Outcome::Action);
+        self.accepted(path, polling, &id, base)
+    }
+
+    // accepted returns the 202 response of a DELETE or POST LRO.
+    fn accepted(&self, path: &str, polling: Polling, id: &str, base: &str) -> MockResponse {
+        let resp =
+            MockResponse::new(202, None).with_header("Retry-After", self.retry_after.to_string());
+        match polling {
+            Polling::AsyncOp => resp
+                .with_header("Azure-AsyncOperation", operation_url(base, path, id))
+                .with_header("Location", result_url(base, path, id)),
+            Polling::Op => resp.with_header("Operation-Location", operation_url(base, path, id)),
+            Polling::Loc | Polling::Body => {
+                resp.with_header("Location", result_url(base, path, id))
+            }
+        }
+    }
+
+    fn start(&mut self, key: String, polling: Polling, outcome: Outcome) -> String {
+        self.next_operation += 1;
+        let id = format!("op{}", self.next_operation);
+        let op = Operation {
+            key,
+            polling,
+            outcome,
+            remaining_polls: self.polls,
+            start_time: chrono::Utc::now().to_rfc3339(),
+        };
+        self.operations.insert(id.clone(), op);
+        if self.polls == 0 {
+            self.complete(&id);
+        }
+        id
+    }
+
+    // advance counts a poll of the LRO, which completes it once there are no more polls remaining.
+    fn advance(&mut self, id: &str) {
+        let Some(op) = self.operations.get_mut(id) else {
+            return;
+        };
+        if op.done() {
+            return;
+        }
+        op.remaining_polls -= 1;
+        if op.done() {
+            self.complete(id);
+        }
+    }
+
+    fn complete(&mut self, id: &str) {
+        let Some(op) = self.operations.get(id).cloned() else {
+            return;
+        };
+        match op.outcome {
+            Outcome::Provisioned => {
+                if let Some(properties) = self
+                    .resources
+                    .get_mut(&op.key)
+                    .and_then(|r| r.get_mut("properties"))
+                    .and_then(Value::as_object_mut)
+                {
+                    properties.insert("provisioningState".to_string(), json!("Succeeded"));
+                }
+            }
+            Outcome::Deleted => self.remove(&op.key),
+            Outcome::Action => {}
+        }
+    }
+
+    // remove deletes the resource, together with its child resources.
+    fn remove(&mut self, key: &str) {
+        let prefix = format!("{key}/");
+        self.resources
+            .retain(|k, _| k != key && !k.starts_with(&prefix));
+    }
+
+    // operation_status serves the status of the LRO, for the Azure-AsyncOperation and Operation-Location polling.
+    fn operation_status(&mut self, id: &str) -> MockResponse {
+        self.advance(id);
+        let Some(op) = self.operations.get(id) else {
+            return not_found(id);
+        };
+        let total = self.polls.max(1);
+        let mut body = json!({
+            "id": id,
+            "name": id,
+            "status": if op.done() { "Succeeded" } else { "InProgress" },
+            "startTime": op.start_time,
+            "percentComplete": f64::from(total - op.remaining_polls) * 100.0 / f64::from(total),
+        });
+        if op.polling == Polling::Op && op.outcome == Outcome::Provisioned {
+            body["resourceLocation"] = json!(self.resources.get(&op.key).and_then(|r| r.get("id")));
+        }
+        let resp = MockResponse::new(200, Some(body));
+        match op.done() {
+            true => resp,
+            false => resp.with_header("Retry-After", self.retry_after.to_string()),
+        }
+    }
+
+    // operation_result serves the Location polling, which is 202 until the LRO completes.
+    fn operation_result(&mut self, id: &str, base: &str) -> MockResponse {
+        self.advance(id);
+        let Some(op) = self.operations.get(id) else {
+            return not_found(id);
+        };
+        if !op.done() {
+            let path = self
+                .resources
+                .get(&op.key)
+                .and_then(|r| r.get("id"))
+                .and_then(Value::as_str)
+                .unwrap_or(&op.key)
+                .to_string();
+            return MockResponse::new(202, None)
+                .with_header("Location", result_url(base, &path, id))
+                .with_header("Retry-After", self.retry_after.to_string());
+        }
+        match op.outcome {
+            Outcome::Deleted => MockResponse::new(204, None),
+            Outcome::Provisioned => MockResponse::new(200, self.resources.get(&op.key).cloned()),
+            Outcome::Action => MockResponse::new(200, Some(json!({}))),
+        }
+    }
+}
+
+fn segments(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// subscription returns the "/subscriptions/{id}" prefix of the path.
+fn subscription(path: &str) -> String {
+    segments(path)
+        .iter()
+        .take(2)
+        .fold(String::new(), |acc, s| format!("{acc}/{s}"))
+}
+
+fn operation_url(base: &str, path: &str, id: &str) -> String {
+    format!(
+        "{base}{}/providers/Microsoft.Mock/operations/{id}",
+        subscription(path)
+    )
+}
+
+fn result_url(base: &str, path: &str, id: &str) -> String {
+    format!(
+        "{base}{}/providers/Microsoft.Mock/operationResults/{id}",
+        subscription(path)
+    )
+}
+
+// parent returns the id of the parent resource, which must exist before the child is created.
+// The resource groups have no parent, as the subscriptions are not emulated.
+fn parent(path: &str) -> Option<String> {
+    let mut segs = segments(path);
+    segs.truncate(segs.len().saturating_sub(2));
+    if segs.len() >= 2 && segs[segs.len() - 2].eq_ignore_ascii_case("providers") {
+        segs.truncate(segs.len() - 2);
+    }
+    if segs.len() < 4 {
+        return None;
+    }
+    Some(format!("/{}", segs.join("/")))
+}
+
+// resource_type returns the type of the resource, e.g. "Microsoft.Network/virtualNetworks/subnets".
+fn resource_type(path: &str) -> String {
+    let segs = segments(path);
+    match segs
+        .iter()
+        .position(|s| s.eq_ignore_ascii_case("providers"))
+    {
+        Some(i) if i + 1 < segs.len() => {
+            let types: Vec<&str> = segs[i + 2..]
+                .iter()
+                .step_by(2)
+                .map(String::as_str)
+                .collect();
+            format!("{}/{}", segs[i + 1], types.join("/"))
+        }
+        _ => "Microsoft.Resources/resourceGroups".to_string(),
+    }
+}
+
+fn not_found(path: &str) -> MockResponse {
+    MockResponse::error(
+        404,
+        "ResourceNotFound",
+        format!("The resource '{path}' was not found."),
+    )
+}
+
+// merge merges the patch into the value, as a JSON merge patch.
+fn merge(value: &mut Value, patch: Value) {
+    match (value, patch) {
+        (Value::Object(value), Value::Object(patch)) => {
+            for (k, v) in patch {
+                if v.is_null() {
+                    value.remove(&k);
+                } else {
+                    merge(value.entry(k).or_insert(Value::Null), v);
+                }
+            }
+        }
+        (value, patch) => *value = patch,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const BASE: &str = "http://127.0.0.1:8080";
+    const RG: &str = "/subscriptions/sub/resourceGroups/rg";
+    const VNET: &str =
+        "/subscriptions/sub/resourceGroups/rg/providers/Microsoft.Network/virtualNetworks/vnet";
+
+    fn route(method: Method, path: &str, long_running: Option<&str>) -> Route {
+        Route {
+            method,
+            template: segments(path),
+            long_running: long_running.map(str::to_string),
+            pageable: false,
+        }
+    }
+
+    fn emulator(vnet_lro: &str) -> Emulator {
+        let rg = "/subscriptions/{subscriptionId}/resourcegroups/{resourceGroupName}";
+        let vnet = "/subscriptions/{subscriptionId}/resourceGroups/{resourceGroupName}/providers/Microsoft.Network/virtualNetworks/{virtualNetworkName}";
+        Emulator::new(
+            vec![
+                route(Method::Get, rg, None),
+                route(Method::Put, rg, None),
+                route(Method::Delete, rg, Some("azure-async-operation")),
+                route(Method::Get, vnet, None),
+                route(Method::Put, vnet, Some(vnet_lro)),
+                route(Method::Delete, vnet, Some("location")),
+            ],
+            2,
+            0,
+        )
+    }
+
+    fn header<'a>(resp: &'a MockResponse, name: &str) -> &'a str {
+        resp.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+            .unwrap()
+    }
+
+    fn state(resp: &MockResponse) -> &str {
+        resp.body.as_ref().unwrap()["properties"]["provisioningState"]
+            .as_str()
+            .unwrap()
+    }
+
+    #[test]
+    fn group() {
+        let mut e = emulator("azure-async-operation");
+        assert_eq!(e.handle(Method::Get, RG, "", BASE).status, 404);
+
+        let resp = e.handle(Method::Put, RG, r#"{"location": "westus"}"#, BASE);
+        assert_eq!(resp.status, 201);
+        assert_eq!(
+            resp.body,
+            Some(json!({
+                "id": RG,
+                "name": "rg",
+                "type": "Microsoft.Resources/resourceGroups",
+                "location": "westus",
+                "properties": {"provisioningState": "Succeeded"},
+            }))
+        );
+        assert_eq!(e.handle(Method::Get, RG, "", BASE).status, 200);
+
+        // The child resources are deleted along with the group
+        e.handle(Method::Put, VNET, "{}", BASE);
+        let resp = e.handle(
+            Method::Delete,
+            &format!("{RG}?api-version=2022-09-01"),
+            "",
+            BASE,
+        );
+        assert_eq!(resp.status, 202);
+        let status = header(&resp, "Azure-AsyncOperation")
+            .trim_start_matches(BASE)
+            .to_string();
+        assert_eq!(
+            status,
+            "/subscriptions/sub/providers/Microsoft.Mock/operations/op2"
+        );
+        assert_eq!(
+            e.handle(Method::Get, &status, "", BASE).body.unwrap()["status"],
+            "InProgress"
+        );
+        assert_eq!(
+            e.handle(Method::Get, &status, "", BASE).body.unwrap()["status"],
+            "Succeeded"
+        );
+        assert_eq!(e.handle(Method::Get, RG, "", BASE).status, 404);
+        assert_eq!(e.handle(Method::Get, VNET, "", BASE).status, 404);
+    }
+
+    #[test]
+    fn polling() {
+        // The parent resource group must exist
+        let mut e = emulator("azure-async-operation");
+        assert_eq!(e.handle(Method::Put, VNET, "{}", BASE).status, 404);
+
+        // Body polling
+        let mut e = emulator("original-uri");
+        e.handle(Method::Put, RG, "{}", BASE);
+        let resp = e.handle(Method::Put, VNET, "{}", BASE);
+        assert_eq!((resp.status, state(&resp)), (201, "Creating"));
+        assert!(resp.headers.is_empty());
+        assert_eq!(state(&e.handle(Method::Get, VNET, "", BASE)), "Creating");
+        assert_eq!(state(&e.handle(Method::Get, VNET, "", BASE)), "Succeeded");
+
+        // Operation-Location polling
+        let mut e = emulator("operation-location");
+        e.handle(Method::Put, RG, "{}", BASE);
+        let resp = e.handle(Method::Put, VNET, "{}", BASE);
+        let status = header(&resp, "Operation-Location")
+            .trim_start_matches(BASE)
+            .to_string();
+        e.handle(Method::Get, &status, "", BASE);
+        let body = e.handle(Method::Get, &status, "", BASE).body.unwrap();
+        assert_eq!(body["status"], "Succeeded");
+        assert_eq!(body["resourceLocation"], VNET);
+        assert_eq!(state(&e.handle(Method::Get, VNET, "", BASE)), "Succeeded");
+
+        // Location polling
+        let resp = e.handle(Method::Delete, VNET, "", BASE);
+        let location = header(&resp, "Location")
+            .trim_start_matches(BASE)
+            .to_string();
+        assert_eq!(e.handle(Method::Get, &location, "", BASE).status, 202);
+        assert_eq!(e.handle(Method::Get, &location, "", BASE).status, 204);
+        assert_eq!(e.handle(Method::Get, VNET, "", BASE).status, 404);
+    }
+
+    #[test]
+    fn subscriptions() {
+        let mut e = emulator("location");
+        let resp = e.handle(
+            Method::Get,
+            "/subscriptions?api-version=2022-12-01",
+            "",
+            BASE,
+        );
+        assert_eq!(resp.status, 200);
+        assert_eq!(
+            resp.body.unwrap()["value"][0]["subscriptionId"],
+            "00000000-0000-0000-0000-000000000000"
+        );
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(parent(VNET), Some(RG.to_string()));
+        assert_eq!(
+            parent(&format!("{VNET}/subnets/default")),
+            Some(VNET.to_string())
+        );
+        assert_eq!(parent(RG), None);
+        assert_eq!(
+            resource_type(&format!("{VNET}/subnets/default")),
+            "Microsoft.Network/virtualNetworks/subnets"
+        );
+    }
+}
@@ -1,27 +1,133 @@
 use anyhow::Result;
-use azure::client::Client;
+use azure::client::{CancellationToken, Client, PollProgress, ProgressHandler};
+use azure::error::ErrorCategory;
 use azure::run;
+use azure_core::http::Context;
 use azure_identity::DefaultAzureCredential;
+use std::io::{IsTerminal, Write};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::{env, path::PathBuf, str::FromStr};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    match run_cli().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // The usage error, help and version are printed by clap, which tells the exit code as well
+            if let Some(e) = e.downcast_ref::<clap::Error>() {
+                e.print().ok();
+                return match e.use_stderr() {
+                    true => ExitCode::from(ErrorCategory::Usage.exit_code()),
+                    false => ExitCode::SUCCESS,
+                };
+            }
+            eprintln!("Error: {e:?}");
+            ExitCode::from(ErrorCategory::of(&e).exit_code())
+        }
+    }
+}
+
+async fn run_cli() -> Result<()> {
     let credential = DefaultAzureCredential::new()?;
-    let client = Client::new(
-        "https://management.azure.com",
+    // AZURE_RS_ENDPOINT overrides the ARM endpoint, e.g. to run against azure-mock
+    let endpoint =
+        env::var("AZURE_RS_ENDPOINT").unwrap_or("https://management.azure.com".to_string());
+    let mut client = Client::new(
+        &endpoint,
         vec!["https://management.azure.com/.default"],
         credential,
         None,
     )?;
+    let spinner = Spinner::new();
+    if std::io::stderr().is_terminal() {
+        let spinner = spinner.clone();
+        client = client.with_progress(ProgressHandler::new(move |p| spinner.update(p)));
+    }
+    let token = CancellationToken::new();
+    tokio::spawn(cancel_on_ctrl_c(token.clone()));
     let res = run(
         PathBuf::from_str("./metadata")?,
         &client,
-        env::args_os()
-            .into_iter()
-            .map(|s| s.into_string().unwrap())
-            .collect(),
+        env::args_os().map(|s| s.into_string().unwrap()).collect(),
+        &Context::new().with_value(token),
     )
-    .await?;
-    println!("{res}");
+    .await;
+    spinner.stop();
+    let res = res?;
+    if !res.is_empty() {
+        println!("{res}");
+    }
     Ok(())
 }
+
+// cancel_on_ctrl_c cancels the command on the first Ctrl-C, so that it stops gracefully and reports the state of
+// the long running operation. The second Ctrl-C exits immediately.
+async fn cancel_on_ctrl_c(token: CancellationToken) {
+    if tokio::signal::ctrl_c().await.is_err() {
+        return;
+    }
+    token.cancel();
+    if tokio::signal::ctrl_c().await.is_ok() {
+        std::process::exit(ErrorCategory::Cancelled.exit_code().into());
+    }
+}
+
+// Spinner renders the progress of the long running operation on stderr.
+// It starts spinning on the first progress report, until it is stopped.
+#[derive(Clone, Default)]
+struct Spinner {
+    // The latest progress message, None means the spinner is not started (or has stopped).
+    message: Arc<Mutex<Option<String>>>,
+}
+
+impl Spinner {
+    const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn update(&self, progress: &PollProgress) {
+        let mut message = format!("Running ({})", progress.status);
+        if let Some(percent) = progress.percent_complete {
+            message = format!("Running ({}, {percent:.0}%)", progress.status);
+        }
+        if let Some(start_time) = &progress.start_time {
+            message.push_str(&format!(", started at {start_time}"));
+        }
+        if progress.next_delay.is_none() {
+            self.stop();
+            return;
+        }
+        let started = self.message.lock().unwrap().replace(message).is_some();
+        if !started {
+            let this = self.clone();
+            std::thread::spawn(move || this.spin());
+        }
+    }
+
+    fn spin(&self) {
+        for frame in Self::FRAMES.iter().cycle() {
+            // Hold the lock while rendering, so that it won't render after being stopped
+            {
+                let message = self.message.lock().unwrap();
+                let Some(message) = message.as_ref() else {
+                    break;
+                };
+                eprint!("\r\x1b[2K{frame} {message}");
+                std::io::stderr().flush().ok();
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn stop(&self) {
+        let mut message = self.message.lock().unwrap();
+        if message.take().is_some() {
+            eprint!("\r\x1b[2K");
+            std::io::stderr().flush().ok();
+        }
+    }
+}
@@ -1,7 +1,12 @@
-mod poller;
+mod cancel;
+mod logging;
+pub mod poller;
+#[cfg(not(target_arch = "wasm32"))]
+mod recording;
 mod response;
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use azure_core::{
     credentials::TokenCredential,
@@ -13,13 +18,49 @@ use azure_core::{
 };
 use bytes::Bytes;
 
-use crate::client::poller::Poller;
-use crate::client::response::Response;
+pub(crate) use crate::client::cancel::interrupted;
+pub use crate::client::cancel::{CancellationToken, Deadline, RequestTimeout};
+use crate::client::logging::LoggingPolicy;
+pub use crate::client::logging::{HttpLogger, LogLevel};
+pub use crate::client::poller::{
+    FinalStateVia, NewPollerOptions, PollProgress, PollUntilDoneOptions, Poller, PollerHandle,
+    ProgressHandler,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::client::recording::{
+    Cassette, Interaction, RecordedRequest, RecordedResponse, Recording,
+};
+pub use crate::client::response::Response;
+
+/// Options for [`Client::new`].
+#[derive(Clone, Debug, Default)]
+pub struct ClientNewOptions {
+    pub client_options: ClientOptions,
+    /// Records the HTTP interactions to, or replays them from a cassette.
+    /// None means the mode is specified by the environment variables, see [`Recording::from_env`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub recording: Option<Recording>,
+}
+
+/// Options for [`Client::run`].
+#[derive(Clone, Debug, Default)]
+pub struct ClientRunOptions<'a> {
+    pub method_options: ClientMethodOptions<'a>,
+    /// Additional headers sent with the request, e.g. `If-Match`.
+    pub headers: Vec<(String, String)>,
+    /// Whether the operation is a LRO, as declared by the metadata.
+    /// None means unknown, in which case a PUT/POST/PATCH/DELETE is detected as a LRO from the response headers.
+    pub long_running: Option<bool>,
+    /// Options for the poller of the LRO.
+    pub poller_options: Option<NewPollerOptions>,
+}
 
 #[derive(Debug)]
 pub struct Client {
     endpoint: Url,
     pipeline: Pipeline,
+    poll_options: PollUntilDoneOptions,
+    request_timeout: Option<Duration>,
 }
 
 impl Client {
@@ -27,54 +68,190 @@ impl Client {
         endpoint: &str,
         auth_scopes: impl IntoIterator<Item = impl Into<String>>,
         credential: Arc<dyn TokenCredential>,
-        options: Option<ClientOptions>,
+        options: Option<ClientNewOptions>,
     ) -> Result<Self> {
         let endpoint = Url::parse(endpoint)?;
         let options = options.unwrap_or_default();
-        let auth_policy: Arc<dyn Policy> =
-            Arc::new(BearerTokenCredentialPolicy::new(credential, auth_scopes));
+        #[allow(unused_mut)]
+        let mut client_options = options.client_options;
+        let mut per_call_policies: Vec<Arc<dyn Policy>> = vec![];
+        // Never send the token over plain HTTP, which is only expected for a local emulator (e.g. azure-mock)
+        if endpoint.scheme() == "https" {
+            per_call_policies.push(Arc::new(BearerTokenCredentialPolicy::new(
+                credential,
+                auth_scopes,
+            )));
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(recording) = match options.recording {
+            Some(recording) => Some(recording),
+            None => Recording::from_env()?,
+        } {
+            client_options.transport = Some(azure_core::http::TransportOptions::new(
+                recording.http_client()?,
+            ));
+            // The replayed requests never reach Azure, there is no need to authenticate
+            if recording.is_playback() {
+                per_call_policies.clear();
+            }
+        }
         let pipeline = Pipeline::new(
             option_env!("CARGO_PKG_NAME"),
             option_env!("CARGO_PKG_VERSION"),
-            options,
-            vec![auth_policy],
-            vec![],
+            client_options,
+            per_call_policies,
+            // Logged per try, so that the retries are logged as well
+            vec![Arc::new(LoggingPolicy)],
         );
-        Ok(Self { endpoint, pipeline })
+        Ok(Self {
+            endpoint,
+            pipeline,
+            poll_options: PollUntilDoneOptions::default(),
+            request_timeout: None,
+        })
+    }
+
+    /// Subscribes to the progress of the LROs, which is reported after each poll.
+    pub fn with_progress(mut self, progress: ProgressHandler) -> Self {
+        self.poll_options.progress = Some(progress);
+        self
+    }
+
+    /// Bounds each request sent by the client, unless the context specifies a [`RequestTimeout`].
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the options to poll the LROs, e.g. the overall timeout.
+    /// The progress handler set by [`Client::with_progress`] is kept, unless the options specify one.
+    pub fn with_poll_options(mut self, options: PollUntilDoneOptions) -> Self {
+        let progress = self.poll_options.progress.take();
+        self.poll_options = PollUntilDoneOptions {
+            progress: options.progress.or(progress),
+            ..options
+        };
+        self
     }
 
     pub async fn run(
         &self,
         method: Method,
         api_path: &str,
-        api_version: &str,
+        query: &[(String, String)],
         body: Option<Bytes>,
-        options: Option<ClientMethodOptions<'_>>,
+        options: Option<ClientRunOptions<'_>>,
     ) -> Result<Response> {
         let options = options.unwrap_or_default();
+        let ctx = Context::with_context(&options.method_options.context);
+        let (resp, poller) = self
+            .begin(method, api_path, query, body, Some(options.clone()))
+            .await?;
+        match poller {
+            Some(mut poller) => self.wait(&ctx, &mut poller).await,
+            None => Ok(resp),
+        }
+    }
+
+    // begin sends the request without waiting for the LRO to complete.
+    // It returns the initial response, together with the poller if the operation can be a LRO.
+    pub async fn begin(
+        &self,
+        method: Method,
+        api_path: &str,
+        query: &[(String, String)],
+        body: Option<Bytes>,
+        options: Option<ClientRunOptions<'_>>,
+    ) -> Result<(Response, Option<Poller>)> {
+        let ClientRunOptions {
+            method_options: options,
+            headers,
+            long_running,
+            poller_options,
+        } = options.unwrap_or_default();
         let mut url = self.endpoint.clone();
         url = url.join(api_path)?;
-        url.query_pairs_mut()
-            .append_pair("api-version", api_version);
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
         let mut request = Request::new(url, method);
         request.insert_header("accept", "application/json");
+        for (name, value) in headers {
+            request.insert_header(name, value);
+        }
         if let Some(body) = body {
             request.insert_header("content-type", "application/json");
             request.set_body(body);
         }
 
         let ctx = Context::with_context(&options.context);
-        let raw_resp = self.pipeline.send(&ctx, &mut request).await?;
-        let resp = Response::from_raw_response(raw_resp).await?;
+        let resp = self.send(&ctx, &mut request).await?;
 
-        // For PUT, POST, PATCH, DELETE operations that can be a LRO, try to
-        if [Method::Put, Method::Post, Method::Delete, Method::Patch].contains(&method) {
-            if let Ok(mut poller) = Poller::new(self.pipeline.clone(), &request, &resp, None).await
+        let poller = match long_running {
+            // Leave the error response to the caller
+            Some(true) if !resp.status_code.is_success() => None,
+            Some(true) => {
+                Some(Poller::new(self.pipeline.clone(), &request, &resp, poller_options).await?)
+            }
+            Some(false) => None,
+            // For PUT, POST, PATCH, DELETE operations that can be a LRO, try to detect it from the response
+            None if [Method::Put, Method::Post, Method::Delete, Method::Patch]
+                .contains(&method) =>
             {
-                return poller.poll_until_done(&ctx, None).await;
+                Poller::new(self.pipeline.clone(), &request, &resp, poller_options)
+                    .await
+                    .ok()
             }
-        }
+            None => None,
+        };
+        Ok((resp, poller))
+    }
+
+    // wait polls the LRO until it reaches a terminal state, and returns its result.
+    pub async fn wait(&self, ctx: &Context<'_>, poller: &mut Poller) -> Result<Response> {
+        poller
+            .poll_until_done(&self.context(ctx), Some(self.poll_options.clone()))
+            .await
+    }
+
+    // resume resumes the poller of a LRO from its handle, which is previously obtained from Poller::handle.
+    pub fn resume(&self, handle: &PollerHandle) -> Result<Poller> {
+        Poller::from_handle(self.pipeline.clone(), handle)
+    }
+
+    // run_next_link fetches the next page of a pageable operation.
+    // The next link is an absolute URL that carries its own query string (e.g. the api-version), so it is used as is.
+    pub async fn run_next_link(
+        &self,
+        next_link: &str,
+        options: Option<ClientMethodOptions<'_>>,
+    ) -> Result<Response> {
+        let options = options.unwrap_or_default();
+        let url = self.endpoint.join(next_link)?;
+        let mut request = Request::new(url, Method::Get);
+        request.insert_header("accept", "application/json");
 
-        Ok(resp)
+        let ctx = Context::with_context(&options.context);
+        self.send(&ctx, &mut request).await
+    }
+
+    // send sends the request, which is bounded by the request timeout, and stops once the command is cancelled.
+    async fn send(&self, ctx: &Context<'_>, request: &mut Request) -> Result<Response> {
+        let ctx = self.context(ctx);
+        cancel::request(&ctx, async {
+            let raw_resp = self.pipeline.send(&ctx, request).await?;
+            Response::from_raw_response(raw_resp).await
+        })
+        .await
+    }
+
+    // context returns the context that carries the request timeout of the client, if any.
+    fn context<'a>(&self, ctx: &'a Context<'_>) -> Context<'a> {
+        match self.request_timeout {
+            Some(timeout) if ctx.value::<RequestTimeout>().is_none() => {
+                Context::with_context(ctx).with_value(RequestTimeout(timeout))
+            }
+            _ => Context::with_context(ctx),
+        }
     }
 }
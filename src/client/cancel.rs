@@ -0,0 +1,227 @@
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+use azure_core::error::ErrorKind;
+use azure_core::http::Context;
+use azure_core::{Error, Result};
+use chrono::{DateTime, Utc};
+use futures::future::{self, Either};
+use typespec_client_core::sleep::sleep;
+use typespec_client_core::time;
+
+use crate::error::{InterruptReason, Interrupted};
+
+/// CancellationToken cancels a command cooperatively, which stops its in-flight request and the LRO polling.
+///
+/// It is passed down via the [`Context`], e.g. `Context::new().with_value(token.clone())`.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<Mutex<CancellationState>>);
+
+#[derive(Debug, Default)]
+struct CancellationState {
+    cancelled: bool,
+    wakers: Vec<Waker>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.cancelled = true;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.lock().unwrap().cancelled
+    }
+
+    // cancelled completes once the token is cancelled.
+    pub async fn cancelled(&self) {
+        poll_fn(|cx| {
+            let mut state = self.0.lock().unwrap();
+            if state.cancelled {
+                return Poll::Ready(());
+            }
+            if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                state.wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+/// Deadline is the time by which the command must complete, which is passed down via the [`Context`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Deadline(DateTime<Utc>);
+
+impl Deadline {
+    // after returns the deadline after the timeout from now, which saturates at the max time for a huge timeout.
+    pub fn after(timeout: Duration) -> Self {
+        Self(deadline_after(timeout).unwrap_or(DateTime::<Utc>::MAX_UTC))
+    }
+
+    fn remaining(&self) -> Duration {
+        (self.0 - Utc::now()).to_std().unwrap_or_default()
+    }
+}
+
+// deadline_after returns the time after the timeout from now, or None if it is beyond the representable time, which
+// means there is no deadline in effect.
+pub(crate) fn deadline_after(timeout: Duration) -> Option<DateTime<Utc>> {
+    Utc::now().checked_add_signed(chrono::Duration::from_std(timeout).ok()?)
+}
+
+/// RequestTimeout bounds each request sent by the client, which is passed down via the [`Context`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RequestTimeout(pub Duration);
+
+// interruptible runs the future until it completes, unless the command is cancelled or reaches its deadline first.
+pub(crate) async fn interruptible<T>(
+    ctx: &Context<'_>,
+    fut: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    race(ctx, None, fut).await
+}
+
+// request is like interruptible, besides it fails the request that exceeds the RequestTimeout in the context.
+pub(crate) async fn request<T>(
+    ctx: &Context<'_>,
+    fut: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    race(ctx, ctx.value::<RequestTimeout>().map(|t| t.0), fut).await
+}
+
+async fn race<T>(
+    ctx: &Context<'_>,
+    timeout: Option<Duration>,
+    fut: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let token = ctx.value::<CancellationToken>();
+    let deadline = ctx.value::<Deadline>().map(Deadline::remaining);
+    if token.is_some_and(CancellationToken::is_cancelled) {
+        return Err(Error::new(
+            ErrorKind::Other,
+            Interrupted::new(InterruptReason::Cancelled),
+        ));
+    }
+    if token.is_none() && deadline.is_none() && timeout.is_none() {
+        return fut.await;
+    }
+
+    let cancelled = pin!(async {
+        match token {
+            Some(token) => token.cancelled().await,
+            None => future::pending::<()>().await,
+        }
+    });
+    let expired = pin!(async {
+        // The request times out if it is sooner than the deadline
+        let (delay, request_timed_out) = match (deadline, timeout) {
+            (Some(deadline), Some(timeout)) if timeout < deadline => (timeout, true),
+            (Some(deadline), _) => (deadline, false),
+            (None, Some(timeout)) => (timeout, true),
+            (None, None) => return future::pending::<bool>().await,
+        };
+        sleep(time::Duration::try_from(delay).unwrap_or(time::Duration::MAX)).await;
+        request_timed_out
+    });
+    let interrupt = pin!(async {
+        match future::select(cancelled, expired).await {
+            Either::Left(_) => Error::new(
+                ErrorKind::Other,
+                Interrupted::new(InterruptReason::Cancelled),
+            ),
+            Either::Right((true, _)) => Error::message(
+                ErrorKind::Io,
+                format!(
+                    "the request timed out after {}s",
+                    timeout.unwrap_or_default().as_secs()
+                ),
+            ),
+            Either::Right((false, _)) => Error::new(
+                ErrorKind::Other,
+                Interrupted::new(InterruptReason::TimedOut),
+            ),
+        }
+    });
+    match future::select(pin!(fut), interrupt).await {
+        Either::Left((res, _)) => res,
+        Either::Right((err, _)) => Err(err),
+    }
+}
+
+// interrupted returns the interruption that fails the request, if any.
+pub(crate) fn interrupted(err: &Error) -> Option<&Interrupted> {
+    err.downcast_ref::<Interrupted>()
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn reason(err: &Error) -> Option<InterruptReason> {
+        err.downcast_ref::<Interrupted>().map(|i| i.reason)
+    }
+
+    #[tokio::test]
+    async fn race() {
+        let ctx = Context::new();
+        assert_eq!(interruptible(&ctx, async { Ok(1) }).await.unwrap(), 1);
+
+        let token = CancellationToken::new();
+        let ctx = Context::new().with_value(token.clone());
+        let res = interruptible(&ctx, async {
+            token.cancel();
+            future::pending::<Result<()>>().await
+        })
+        .await;
+        assert_eq!(reason(&res.unwrap_err()), Some(InterruptReason::Cancelled));
+        let res = interruptible(&ctx, async { Ok(1) }).await;
+        assert_eq!(reason(&res.unwrap_err()), Some(InterruptReason::Cancelled));
+
+        let ctx = Context::new().with_value(Deadline::after(Duration::from_millis(10)));
+        let res = interruptible(&ctx, future::pending::<Result<()>>()).await;
+        assert_eq!(reason(&res.unwrap_err()), Some(InterruptReason::TimedOut));
+
+        let ctx = Context::new()
+            .with_value(Deadline::after(Duration::from_secs(60)))
+            .with_value(RequestTimeout(Duration::from_millis(10)));
+        let err = request(&ctx, future::pending::<Result<()>>())
+            .await
+            .unwrap_err();
+        assert_eq!(reason(&err), None);
+        assert_eq!(err.kind(), &ErrorKind::Io);
+    }
+
+    #[test]
+    fn deadline() {
+        let deadline = deadline_after(Duration::from_secs(60)).unwrap();
+        assert!(deadline > Utc::now() + chrono::Duration::seconds(50));
+        assert_eq!(
+            deadline_after(Duration::from_secs(100_000_000_000_000)),
+            None
+        );
+        assert_eq!(deadline_after(Duration::MAX), None);
+    }
+
+    #[tokio::test]
+    async fn huge_deadline() {
+        let ctx = Context::new().with_value(Deadline::after(Duration::from_secs(u64::MAX)));
+        let res = interruptible(&ctx, async {
+            tokio::task::yield_now().await;
+            Ok(1)
+        })
+        .await;
+        assert_eq!(res.unwrap(), 1);
+    }
+}
@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use azure_core::http::headers::{HeaderName, Headers};
+use azure_core::http::policies::{Policy, PolicyResult};
+use azure_core::http::request::Body;
+use azure_core::http::{Context, RawResponse, Request};
+use serde_json::Value;
+
+// The headers that carry credentials, which are never logged (or recorded).
+pub(crate) const SENSITIVE_HEADERS: [&str; 4] = [
+    "authorization",
+    "x-ms-authorization-auxiliary",
+    "cookie",
+    "set-cookie",
+];
+
+// The headers that identify the request to the service, which are always logged.
+const REQUEST_ID_HEADERS: [&str; 3] = [
+    "x-ms-client-request-id",
+    "x-ms-correlation-request-id",
+    "x-ms-request-id",
+];
+
+const REDACTED: &str = "REDACTED";
+
+/// LogLevel is the verbosity of the HTTP log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// Logs the method, URL, status, request ids and timing of each request.
+    Verbose,
+    /// Logs the headers and bodies as well, with the secrets redacted.
+    Debug,
+}
+
+/// HttpLogger logs the HTTP requests sent by the client, including the polls of the LROs.
+///
+/// It is enabled by adding it to the [`Context`] of the call.
+#[derive(Clone)]
+pub struct HttpLogger {
+    level: LogLevel,
+    sink: Arc<dyn Fn(&str) + Send + Sync>,
+}
+
+impl HttpLogger {
+    pub fn new(level: LogLevel, sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Self {
+            level,
+            sink: Arc::new(sink),
+        }
+    }
+
+    pub fn log(&self, msg: &str) {
+        (self.sink)(msg)
+    }
+}
+
+impl std::fmt::Debug for HttpLogger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HttpLogger({:?})", self.level)
+    }
+}
+
+// LoggingPolicy logs each try of the requests to the HttpLogger in the context, if any.
+#[derive(Debug)]
+pub(crate) struct LoggingPolicy;
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Policy for LoggingPolicy {
+    async fn send(
+        &self,
+        ctx: &Context,
+        request: &mut Request,
+        next: &[Arc<dyn Policy>],
+    ) -> PolicyResult {
+        let Some(logger) = ctx.value::<HttpLogger>().cloned() else {
+            return next[0].send(ctx, request, &next[1..]).await;
+        };
+        let mut msg = format!("Request: {} {}", request.method(), request.url());
+        if logger.level >= LogLevel::Debug {
+            msg.push_str(&format_headers(request.headers()));
+            if let Some(body) = request_body(request) {
+                msg.push_str(&format_body(body));
+            }
+        }
+        logger.log(&msg);
+
+        let start = chrono::Utc::now();
+        let resp = next[0].send(ctx, request, &next[1..]).await;
+        let elapsed = (chrono::Utc::now() - start).num_milliseconds();
+        let resp = match resp {
+            Ok(resp) => resp,
+            Err(e) => {
+                logger.log(&format!(
+                    "Response: {} {} failed after {elapsed}ms: {e}",
+                    request.method(),
+                    request.url()
+                ));
+                return Err(e);
+            }
+        };
+
+        let (status, headers, body) = resp.deconstruct();
+        let body = body.collect().await?;
+        let mut msg = format!("Response: {} ({elapsed}ms)", u16::from(status));
+        if logger.level >= LogLevel::Debug {
+            msg.push_str(&format_headers(&headers));
+            msg.push_str(&format_body(&body));
+        } else {
+            for name in REQUEST_ID_HEADERS {
+                if let Some(value) = headers.get_optional_str(&HeaderName::from_static(name)) {
+                    msg.push_str(&format!("\n    {name}: {value}"));
+                }
+            }
+        }
+        logger.log(&msg);
+        Ok(RawResponse::from_bytes(status, headers, body))
+    }
+}
+
+// request_body returns the body of the request, unless it is a stream.
+#[cfg_attr(target_arch = "wasm32", allow(irrefutable_let_patterns))]
+fn request_body(request: &Request) -> Option<&[u8]> {
+    if let Body::Bytes(body) = request.body() {
+        return Some(body);
+    }
+    None
+}
+
+fn format_headers(headers: &Headers) -> String {
+    let mut headers: Vec<_> = headers
+        .iter()
+        .map(
+            |(name, value)| match SENSITIVE_HEADERS.contains(&name.as_str()) {
+                true => (name.as_str(), REDACTED),
+                false => (name.as_str(), value.as_str()),
+            },
+        )
+        .collect();
+    headers.sort();
+    headers
+        .into_iter()
+        .map(|(name, value)| format!("\n    {name}: {value}"))
+        .collect()
+}
+
+fn format_body(body: &[u8]) -> String {
+    if body.is_empty() {
+        return String::new();
+    }
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut value) => {
+            redact(&mut value);
+            format!("\n{value}")
+        }
+        Err(_) => format!("\n<{} bytes>", body.len()),
+    }
+}
+
+// redact replaces the values of the secret fields in the JSON, e.g. "adminPassword" or "primaryKey".
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map.iter_mut() {
+                if is_secret(k) && !v.is_null() {
+                    *v = Value::String(REDACTED.to_string());
+                } else {
+                    redact(v);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn is_secret(field: &str) -> bool {
+    let field = field.to_lowercase();
+    ["password", "secret", "token", "connectionstring"]
+        .iter()
+        .any(|s| field.contains(s))
+        || ((field.ends_with("key") || field.ends_with("keys"))
+            && !field.ends_with("publickey")
+            && !field.ends_with("publickeys"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn redaction() {
+        let mut headers = Headers::new();
+        headers.insert("authorization", "Bearer secret");
+        headers.insert("x-ms-request-id", "1");
+        assert_eq!(
+            format_headers(&headers),
+            "\n    authorization: REDACTED\n    x-ms-request-id: 1"
+        );
+
+        let body = json!({
+            "name": "vm",
+            "properties": {
+                "osProfile": {"adminUsername": "foo", "adminPassword": "bar", "sshPublicKey": "ssh-rsa"},
+                "ssh": {"publicKeys": [{"path": "/home/foo/.ssh/authorized_keys", "keyData": "ssh-rsa"}]},
+                "primaryKey": "k1",
+                "keys": [{"keyName": "key1", "value": "k2"}],
+                "clientSecret": null,
+            }
+        });
+        assert_eq!(
+            format_body(body.to_string().as_bytes()),
+            format!(
+                "\n{}",
+                json!({
+                    "name": "vm",
+                    "properties": {
+                        "osProfile": {"adminUsername": "foo", "adminPassword": "REDACTED", "sshPublicKey": "ssh-rsa"},
+                        "ssh": {"publicKeys": [{"path": "/home/foo/.ssh/authorized_keys", "keyData": "ssh-rsa"}]},
+                        "primaryKey": "REDACTED",
+                        "keys": "REDACTED",
+                        "clientSecret": null,
+                    }
+                })
+            )
+        );
+        assert_eq!(format_body(b"not json"), "\n<8 bytes>");
+    }
+}
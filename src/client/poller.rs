@@ -6,11 +6,14 @@ mod op;
 mod utils;
 
 use azure_core::error::ErrorKind;
-use azure_core::http::{Method, Request};
+use azure_core::http::{Method, Request, Url};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use std::time::Duration;
 use typespec_client_core::sleep::sleep;
 use typespec_client_core::time;
-use utils::FinalStateVia;
+pub use utils::FinalStateVia;
+use utils::LROStatus;
 
 use azure_core::Error;
 use azure_core::{
@@ -18,6 +21,7 @@ use azure_core::{
     Result,
 };
 
+use super::cancel;
 use super::response::Response;
 
 trait PollingHandler {
@@ -29,6 +33,9 @@ trait PollingHandler {
     // done returns true if the LRO has reached a terminal state.
     fn done(&self) -> bool;
 
+    // status returns the current state of the LRO.
+    fn status(&self) -> LROStatus;
+
     // result must be called once the LRO has reached a terminal state. It returns result of the operation.
     async fn result(&self, ctx: &Context<'_>) -> Result<Response>;
 }
@@ -41,28 +48,131 @@ enum Handler {
     Noop(noop::Poller),
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PollerKind {
+    AsyncOp,
+    Loc,
+    Op,
+    Body,
+    Noop,
+}
+
+/// PollerHandle is the serializable state of a [`Poller`], from which the polling can be resumed later,
+/// e.g. by another process via [`Poller::from_handle`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PollerHandle {
+    pub kind: PollerKind,
+    // The URL to poll the LRO status.
+    #[serde(rename = "pollingUrl")]
+    pub polling_url: Option<String>,
+    // The URL from the Location header, if any.
+    #[serde(rename = "locationUrl")]
+    pub location_url: Option<String>,
+    // The URL of the initial LRO request.
+    #[serde(rename = "originalUrl")]
+    pub original_url: String,
+    // The HTTP method of the initial LRO request.
+    pub method: String,
+    #[serde(rename = "finalStateVia")]
+    pub final_state_via: Option<FinalStateVia>,
+    #[serde(rename = "resultPath")]
+    pub result_path: Option<String>,
+}
+
+impl PollerHandle {
+    fn polling_url(&self) -> Result<Url> {
+        let url = self.polling_url.as_deref().ok_or(Error::message(
+            ErrorKind::DataConversion,
+            "the poller handle lacks the polling URL",
+        ))?;
+        Ok(Url::parse(url)?)
+    }
+
+    fn location_url(&self) -> Result<Option<Url>> {
+        Ok(self.location_url.as_deref().map(Url::parse).transpose()?)
+    }
+
+    fn original_url(&self) -> Result<Url> {
+        Ok(Url::parse(&self.original_url)?)
+    }
+
+    fn method(&self) -> Result<Method> {
+        self.method.parse()
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct NewPollerOptions {
     // final_state contains the final-state-via value for the LRO.
     // NOTE: used only for Azure-AsyncOperation and Operation-Location LROs.
-    final_state: Option<FinalStateVia>,
+    pub final_state: Option<FinalStateVia>,
 
     // operation_location_result_path contains the JSON path to the result's
     // payload when it's included with the terminal success response.
     // NOTE: only used for Operation-Location LROs.
-    operation_location_result_path: Option<String>,
+    pub operation_location_result_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct PollUntilDoneOptions {
     // frequency is the time to wait between polling intervals in absence of a Retry-After header. Allowed minimum is one second.
     // Pass zero to accept the default value (30s).
-    frequency: Option<Duration>,
+    pub frequency: Option<Duration>,
+
+    // progress is called after each poll, to report the progress of the LRO.
+    pub progress: Option<ProgressHandler>,
+
+    // timeout is the overall time to wait for the LRO to complete. None means no limit.
+    pub timeout: Option<Duration>,
+
+    // max_polls is the maximum number of polls before giving up. None means no limit.
+    pub max_polls: Option<u32>,
+
+    // max_retries is the maximum number of consecutive transient failures (408/429/5xx) of the polls before giving up.
+    // Pass None to accept the default value (5).
+    pub max_retries: Option<u32>,
+}
+
+/// PollProgress reports the state of a LRO after each poll.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PollProgress {
+    /// The current status of the LRO, e.g. "InProgress".
+    pub status: String,
+    /// The completion percentage, if reported by the service (e.g. the Azure-AsyncOperation status body).
+    pub percent_complete: Option<f64>,
+    /// The time when the LRO started, if reported by the service.
+    pub start_time: Option<String>,
+    /// The delay before the next poll. None means the LRO has reached a terminal state.
+    pub next_delay: Option<Duration>,
+}
+
+/// ProgressHandler is the callback to receive the [`PollProgress`] of a LRO.
+#[derive(Clone)]
+pub struct ProgressHandler(Arc<dyn Fn(&PollProgress) + Send + Sync>);
+
+impl ProgressHandler {
+    pub fn new(f: impl Fn(&PollProgress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub fn report(&self, progress: &PollProgress) {
+        (self.0)(progress)
+    }
+}
+
+impl std::fmt::Debug for ProgressHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressHandler")
+    }
 }
 
 pub struct Poller {
     handler: Handler,
     resp: Response,
+    // The URL and HTTP method of the initial LRO request.
+    origin_url: Url,
+    method: Method,
 }
 
 impl Poller {
@@ -105,7 +215,8 @@ impl Poller {
             Handler::Loc(loc::Poller::new(pl, resp.clone())?)
         } else if body::Poller::applicable(req, resp) {
             // must test body poller last as it's a subset of the other pollers.
-            // TODO: this is ambiguous for PATCH/PUT if it returns a 200 with no polling headers (sync completion)
+            // NOTE: this is ambiguous for PATCH/PUT if it returns a 200 with no polling headers (sync completion),
+            // unless the caller knows the operation is a LRO, see ClientRunOptions::long_running.
             Handler::Body(body::Poller::new(pl, req, resp.clone())?)
         } else if resp.status_code == StatusCode::Accepted
             && [Method::Delete, Method::Post]
@@ -125,9 +236,59 @@ impl Poller {
         Ok(Self {
             handler,
             resp: resp.clone(),
+            origin_url: req.url().clone(),
+            method: req.method(),
         })
     }
 
+    /// Resumes the poller from the handle, which is obtained from [`Poller::handle`].
+    pub fn from_handle(pl: Pipeline, handle: &PollerHandle) -> Result<Self> {
+        let handler = match handle.kind {
+            PollerKind::AsyncOp => Handler::AsyncOp(asyncop::Poller::from_handle(pl, handle)?),
+            PollerKind::Loc => Handler::Loc(loc::Poller::from_handle(pl, handle)?),
+            PollerKind::Op => Handler::Op(op::Poller::from_handle(pl, handle)?),
+            PollerKind::Body => Handler::Body(body::Poller::from_handle(pl, handle)?),
+            PollerKind::Noop => Handler::Noop(noop::Poller::new(&Response::pending())),
+        };
+        Ok(Self {
+            handler,
+            resp: Response::pending(),
+            origin_url: handle.original_url()?,
+            method: handle.method()?,
+        })
+    }
+
+    /// Returns the handle of the poller, which can be persisted to resume the polling later.
+    pub fn handle(&self) -> PollerHandle {
+        match &self.handler {
+            Handler::AsyncOp(poller) => poller.handle(),
+            Handler::Loc(poller) => poller.handle(&self.origin_url, self.method),
+            Handler::Op(poller) => poller.handle(),
+            Handler::Body(poller) => poller.handle(&self.origin_url, self.method),
+            Handler::Noop(_) => PollerHandle {
+                kind: PollerKind::Noop,
+                polling_url: None,
+                location_url: None,
+                original_url: self.origin_url.to_string(),
+                method: self.method.to_string(),
+                final_state_via: None,
+                result_path: None,
+            },
+        }
+    }
+
+    /// Returns the current status of the LRO, e.g. "InProgress".
+    pub fn status(&self) -> String {
+        match &self.handler {
+            Handler::AsyncOp(poller) => poller.status(),
+            Handler::Loc(poller) => poller.status(),
+            Handler::Op(poller) => poller.status(),
+            Handler::Body(poller) => poller.status(),
+            Handler::Noop(poller) => poller.status(),
+        }
+        .to_string()
+    }
+
     pub async fn poll(&mut self, ctx: &Context<'_>) -> Result<Response> {
         if self.done() {
             return Ok(self.resp.clone());
@@ -151,22 +312,89 @@ impl Poller {
         opts: Option<PollUntilDoneOptions>,
     ) -> Result<Response> {
         let opts = opts.unwrap_or_default();
+        let max_retries = opts.max_retries.unwrap_or(5);
+        let deadline = opts.timeout.and_then(cancel::deadline_after);
 
+        let mut polls = 0;
+        let mut retries = 0;
         loop {
-            let resp = self.poll(ctx).await?;
+            let resp = cancel::request(ctx, self.poll(ctx)).await?;
+            polls += 1;
             if self.done() {
-                return self.result(ctx).await;
+                self.report(&opts, &resp, None);
+                return cancel::request(ctx, self.result(ctx)).await;
             }
 
-            let duration = time::Duration::try_from(
+            if let Some(max_polls) = opts.max_polls
+                && polls >= max_polls
+            {
+                return Err(Error::message(
+                    ErrorKind::Other,
+                    format!("the operation did not complete after {polls} polls"),
+                ));
+            }
+
+            let mut delay = if utils::is_non_terminal_http_status_code(resp.status_code) {
+                // the poll is throttled or failed transiently, retry with exponential backoff
+                retries += 1;
+                if retries > max_retries {
+                    return Err(Error::message(
+                        resp.into(),
+                        format!("polling failed after {max_retries} retries"),
+                    ));
+                }
+                utils::retry_after(&resp).unwrap_or(utils::backoff(retries))
+            } else {
+                retries = 0;
                 utils::retry_after(&resp)
-                    .unwrap_or(opts.frequency.unwrap_or(Duration::from_secs(30))),
-            )
-            .map_err(|e| Error::new(ErrorKind::Other, e))?;
-            sleep(duration).await;
+                    .unwrap_or(opts.frequency.unwrap_or(Duration::from_secs(30)))
+            };
+
+            if let Some(deadline) = deadline {
+                // poll for the last time at the deadline
+                let Ok(remaining) = (deadline - chrono::Utc::now()).to_std() else {
+                    return Err(Error::message(
+                        ErrorKind::Other,
+                        format!(
+                            "the operation did not complete within {}s",
+                            opts.timeout.unwrap_or_default().as_secs()
+                        ),
+                    ));
+                };
+                delay = delay.min(remaining);
+            }
+
+            self.report(&opts, &resp, Some(delay));
+            let duration =
+                time::Duration::try_from(delay).map_err(|e| Error::new(ErrorKind::Other, e))?;
+            cancel::interruptible(ctx, async {
+                sleep(duration).await;
+                Ok(())
+            })
+            .await?;
         }
     }
 
+    fn report(&self, opts: &PollUntilDoneOptions, resp: &Response, next_delay: Option<Duration>) {
+        let Some(progress) = &opts.progress else {
+            return;
+        };
+        let body: Option<serde_json::Value> = serde_json::from_slice(&resp.body).ok();
+        progress.report(&PollProgress {
+            status: self.status(),
+            percent_complete: body
+                .as_ref()
+                .and_then(|b| b.get("percentComplete"))
+                .and_then(serde_json::Value::as_f64),
+            start_time: body
+                .as_ref()
+                .and_then(|b| b.get("startTime"))
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string),
+            next_delay,
+        });
+    }
+
     pub fn done(&self) -> bool {
         match &self.handler {
             Handler::AsyncOp(poller) => poller.done(),
@@ -180,7 +408,7 @@ impl Poller {
     // result returns the final response of the LRO operation when it reaches a terminal state.
     // If the LRO completed successfully, the Response is returned (which can be None).
     // If the LRO failed or was canceled, an Error of ErrorKind::HttpResponse is returned.
-    async fn result(&self, ctx: &Context<'_>) -> Result<Response> {
+    pub async fn result(&self, ctx: &Context<'_>) -> Result<Response> {
         assert!(self.done());
         match &self.handler {
             Handler::AsyncOp(poller) => poller.result(ctx).await,
@@ -191,3 +419,101 @@ impl Poller {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use azure_core::http::ClientOptions;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn handle() {
+        let pl = Pipeline::new(None, None, ClientOptions::default(), vec![], vec![]);
+        let handle = PollerHandle {
+            kind: PollerKind::AsyncOp,
+            polling_url: Some("https://management.azure.com/operations/1".to_string()),
+            location_url: Some("https://management.azure.com/operationResults/1".to_string()),
+            original_url: "https://management.azure.com/foo?api-version=2024-11-01".to_string(),
+            method: "DELETE".to_string(),
+            final_state_via: Some(FinalStateVia::Location),
+            result_path: None,
+        };
+        let encoded = serde_json::to_value(&handle).unwrap();
+        assert_eq!(encoded["kind"], "asyncOp");
+        assert_eq!(encoded["finalStateVia"], "location");
+
+        let poller = Poller::from_handle(pl, &handle).unwrap();
+        assert_eq!(poller.handle(), handle);
+        assert_eq!(poller.status(), "InProgress");
+        assert!(!poller.done());
+    }
+
+    #[test]
+    fn report() {
+        let pl = Pipeline::new(None, None, ClientOptions::default(), vec![], vec![]);
+        let handle = PollerHandle {
+            kind: PollerKind::Loc,
+            polling_url: Some("https://management.azure.com/operationResults/1".to_string()),
+            location_url: None,
+            original_url: "https://management.azure.com/foo".to_string(),
+            method: "DELETE".to_string(),
+            final_state_via: None,
+            result_path: None,
+        };
+        let poller = Poller::from_handle(pl, &handle).unwrap();
+        let reported = Arc::new(std::sync::Mutex::new(vec![]));
+        let opts = PollUntilDoneOptions {
+            progress: Some(ProgressHandler::new({
+                let reported = reported.clone();
+                move |p| reported.lock().unwrap().push(p.clone())
+            })),
+            ..Default::default()
+        };
+        let mut resp = Response::pending();
+        resp.body = r#"{"status": "InProgress", "percentComplete": 42.5, "startTime": "2025-01-01T00:00:00Z"}"#.into();
+        poller.report(&opts, &resp, Some(Duration::from_secs(10)));
+        poller.report(&opts, &Response::pending(), None);
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![
+                PollProgress {
+                    status: "InProgress".to_string(),
+                    percent_complete: Some(42.5),
+                    start_time: Some("2025-01-01T00:00:00Z".to_string()),
+                    next_delay: Some(Duration::from_secs(10)),
+                },
+                PollProgress {
+                    status: "InProgress".to_string(),
+                    percent_complete: None,
+                    start_time: None,
+                    next_delay: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn retry() {
+        let mut resp = Response::pending();
+        resp.headers.insert("retry-after", "10");
+        assert_eq!(utils::retry_after(&resp), Some(Duration::from_secs(10)));
+
+        let mut resp = Response::pending();
+        let t = chrono::Utc::now() + chrono::Duration::seconds(60);
+        resp.headers.insert("retry-after", t.to_rfc2822());
+        let delay = utils::retry_after(&resp).unwrap();
+        assert!(delay > Duration::from_secs(50) && delay <= Duration::from_secs(60));
+
+        let mut resp = Response::pending();
+        let t = chrono::Utc::now() - chrono::Duration::seconds(60);
+        resp.headers.insert("retry-after", t.to_rfc2822());
+        assert_eq!(utils::retry_after(&resp), Some(Duration::ZERO));
+
+        assert_eq!(utils::backoff(1), Duration::from_secs(1));
+        assert_eq!(utils::backoff(3), Duration::from_secs(4));
+        assert_eq!(utils::backoff(10), Duration::from_secs(60));
+
+        assert_eq!(LROStatus::from_str("succeeded"), LROStatus::Succeeded);
+        assert_eq!(LROStatus::from_str("INPROGRESS"), LROStatus::InProgress);
+    }
+}
@@ -5,8 +5,8 @@ use azure_core::http::{Method, Pipeline, Request, StatusCode, Url};
 use crate::client::poller::utils::{self, get_provisioning_state};
 
 use super::utils::result_helper;
-use super::PollingHandler;
 use super::{utils::LROStatus, Response};
+use super::{PollerHandle, PollerKind, PollingHandler};
 
 pub struct Poller {
     pl: Pipeline,
@@ -50,6 +50,27 @@ impl Poller {
             cur_state,
         })
     }
+
+    pub fn handle(&self, origin_url: &Url, method: Method) -> PollerHandle {
+        PollerHandle {
+            kind: PollerKind::Body,
+            polling_url: Some(self.poll_url.to_string()),
+            location_url: None,
+            original_url: origin_url.to_string(),
+            method: method.to_string(),
+            final_state_via: None,
+            result_path: None,
+        }
+    }
+
+    pub fn from_handle(pl: Pipeline, handle: &PollerHandle) -> Result<Self> {
+        Ok(Self {
+            pl,
+            resp: Response::pending(),
+            poll_url: handle.polling_url()?,
+            cur_state: LROStatus::InProgress,
+        })
+    }
 }
 
 impl PollingHandler for Poller {
@@ -68,9 +89,14 @@ impl PollingHandler for Poller {
         let mut req = Request::new(self.poll_url.clone(), Method::Get);
         let resp = self.pl.send(ctx, &mut req).await?;
         let resp = Response::from_raw_response(resp).await?;
+        if utils::is_non_terminal_http_status_code(resp.status_code) {
+            // the request timed out or is being throttled.
+            // preserve the existing state, and let the caller retry later.
+            return Ok(resp);
+        }
         if !utils::is_valid_status_code(resp.status_code) {
             self.resp = resp.clone();
-            return Err(Error::message(resp.into(), "invalid response status code"));
+            return Err(resp.into());
         }
         if resp.status_code == StatusCode::NoContent {
             self.resp = resp.clone();
@@ -95,6 +121,10 @@ impl PollingHandler for Poller {
         Ok(resp)
     }
 
+    fn status(&self) -> LROStatus {
+        self.cur_state
+    }
+
     fn done(&self) -> bool {
         self.cur_state.is_terminal()
     }
@@ -3,11 +3,11 @@ use azure_core::http::{Method, StatusCode};
 
 use azure_core::Result;
 
-use crate::client::poller::utils::{get_provisioning_state, is_non_terminal_http_status_code};
+use crate::client::poller::utils::{self, get_provisioning_state};
 
 use super::utils::result_helper;
-use super::PollingHandler;
 use super::{utils::LROStatus, Response};
+use super::{PollerHandle, PollerKind, PollingHandler};
 
 pub struct Poller {
     pl: Pipeline,
@@ -34,6 +34,27 @@ impl Poller {
             cur_state,
         })
     }
+
+    pub fn handle(&self, origin_url: &Url, method: Method) -> PollerHandle {
+        PollerHandle {
+            kind: PollerKind::Loc,
+            polling_url: Some(self.loc_url.to_string()),
+            location_url: None,
+            original_url: origin_url.to_string(),
+            method: method.to_string(),
+            final_state_via: None,
+            result_path: None,
+        }
+    }
+
+    pub fn from_handle(pl: Pipeline, handle: &PollerHandle) -> Result<Self> {
+        Ok(Self {
+            pl,
+            resp: Response::pending(),
+            loc_url: handle.polling_url()?,
+            cur_state: LROStatus::InProgress,
+        })
+    }
 }
 
 impl PollingHandler for Poller {
@@ -66,7 +87,7 @@ impl PollingHandler for Poller {
         } else if resp.status_code.is_success() {
             // any 2xx other than a 202 indicates success
             self.cur_state = LROStatus::Succeeded;
-        } else if is_non_terminal_http_status_code(resp.status_code) {
+        } else if utils::is_non_terminal_http_status_code(resp.status_code) {
             // the request timed out or is being throttled.
             // DO NOT include this as a terminal failure. preserve
             // the existing state and return the response.
@@ -76,6 +97,10 @@ impl PollingHandler for Poller {
         Ok(self.resp.clone())
     }
 
+    fn status(&self) -> LROStatus {
+        self.cur_state
+    }
+
     fn done(&self) -> bool {
         self.cur_state.is_terminal()
     }
@@ -1,4 +1,4 @@
-use super::{PollingHandler, Response};
+use super::{utils::LROStatus, PollingHandler, Response};
 use azure_core::{
     http::{Context, Request},
     Result,
@@ -27,6 +27,10 @@ impl PollingHandler for Poller {
         Ok(self.resp.clone())
     }
 
+    fn status(&self) -> LROStatus {
+        LROStatus::Succeeded
+    }
+
     fn done(&self) -> bool {
         true
     }
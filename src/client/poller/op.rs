@@ -1,8 +1,8 @@
 use crate::client::poller::utils::{self, get_resource_location, result_helper};
 
 use super::utils::{get_lro_status, get_provisioning_state};
-use super::PollingHandler;
 use super::{utils::LROStatus, FinalStateVia, Response};
+use super::{PollerHandle, PollerKind, PollingHandler};
 use azure_core::error::ErrorKind;
 use azure_core::http::headers::{HeaderName, LOCATION};
 use azure_core::http::{Method, Pipeline, Request, Url};
@@ -68,6 +68,32 @@ impl Poller {
             cur_state,
         })
     }
+
+    pub fn handle(&self) -> PollerHandle {
+        PollerHandle {
+            kind: PollerKind::Op,
+            polling_url: Some(self.op_loc_url.to_string()),
+            location_url: self.loc_url.as_ref().map(Url::to_string),
+            original_url: self.origin_url.to_string(),
+            method: self.method.to_string(),
+            final_state_via: self.final_state,
+            result_path: self.result_path.clone(),
+        }
+    }
+
+    pub fn from_handle(pl: Pipeline, handle: &PollerHandle) -> Result<Self> {
+        Ok(Self {
+            pl,
+            resp: Response::pending(),
+            op_loc_url: handle.polling_url()?,
+            loc_url: handle.location_url()?,
+            origin_url: handle.original_url()?,
+            method: handle.method()?,
+            final_state: handle.final_state_via,
+            result_path: handle.result_path.clone(),
+            cur_state: LROStatus::InProgress,
+        })
+    }
 }
 
 impl PollingHandler for Poller {
@@ -82,9 +108,14 @@ impl PollingHandler for Poller {
         let mut req = Request::new(self.op_loc_url.clone(), Method::Get);
         let resp = self.pl.send(ctx, &mut req).await?;
         let resp = Response::from_raw_response(resp).await?;
+        if utils::is_non_terminal_http_status_code(resp.status_code) {
+            // the request timed out or is being throttled.
+            // preserve the existing state, and let the caller retry later.
+            return Ok(resp);
+        }
         if !utils::is_valid_status_code(resp.status_code) {
             self.resp = resp.clone();
-            return Err(Error::message(resp.into(), "invalid response status code"));
+            return Err(resp.into());
         }
         let status = get_lro_status(&resp)?;
         if let Some(status) = status {
@@ -99,6 +130,10 @@ impl PollingHandler for Poller {
         }
     }
 
+    fn status(&self) -> LROStatus {
+        self.cur_state
+    }
+
     fn done(&self) -> bool {
         self.cur_state.is_terminal()
     }
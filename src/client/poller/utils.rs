@@ -1,6 +1,6 @@
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
-use std::{fmt::Display, time::Duration};
+use std::{fmt::Display, str::FromStr, time::Duration};
 
 use super::Response;
 
@@ -9,15 +9,19 @@ use azure_core::{
     http::{headers::HeaderName, StatusCode, Url},
     Error, Result,
 };
+use serde::{Deserialize, Serialize};
 use serde_json::{from_slice, Value};
 use std::collections::HashMap;
 
-#[allow(dead_code)]
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FinalStateVia {
+    #[serde(rename = "azure-async-operation")]
     AzureAsyncOp,
+    #[serde(rename = "location")]
     Location,
+    #[serde(rename = "original-uri")]
     OriginalUri,
+    #[serde(rename = "operation-location")]
     OperationLocation,
 }
 
@@ -33,6 +37,23 @@ impl Display for FinalStateVia {
     }
 }
 
+impl FromStr for FinalStateVia {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "azure-async-operation" => Ok(FinalStateVia::AzureAsyncOp),
+            "location" => Ok(FinalStateVia::Location),
+            "original-uri" => Ok(FinalStateVia::OriginalUri),
+            "operation-location" => Ok(FinalStateVia::OperationLocation),
+            _ => Err(Error::message(
+                ErrorKind::DataConversion,
+                format!("unknown final-state-via: {s}"),
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LROStatus {
     Unknown,
@@ -62,14 +83,15 @@ impl Display for LROStatus {
 }
 
 impl LROStatus {
+    // from_str parses the status case-insensitively, as some services report e.g. "succeeded".
     pub fn from_str(v: &str) -> Self {
-        match v {
-            "Succeeded" => LROStatus::Succeeded,
-            "Canceled" => LROStatus::Canceled,
-            "Failed" => LROStatus::Failed,
-            "InProgress" => LROStatus::InProgress,
-            "Cancelled" => LROStatus::Cancelled,
-            "Completed" => LROStatus::Completed,
+        match v.to_lowercase().as_str() {
+            "succeeded" => LROStatus::Succeeded,
+            "canceled" => LROStatus::Canceled,
+            "failed" => LROStatus::Failed,
+            "inprogress" => LROStatus::InProgress,
+            "cancelled" => LROStatus::Cancelled,
+            "completed" => LROStatus::Completed,
             _ => LROStatus::Unknown,
         }
     }
@@ -152,13 +174,10 @@ pub fn retry_after(resp: &Response) -> Option<Duration> {
             header: "retry-after",
             to_duration: Duration::from_secs,
             custom: Some(|s| {
-                if let Ok(t) = DateTime::parse_from_rfc2822(s) {
-                    let now = Utc::now();
-                    let d = now.signed_duration_since(t.with_timezone(&Utc));
-                    Some(Duration::from_millis(d.num_milliseconds() as u64))
-                } else {
-                    None
-                }
+                let t = DateTime::parse_from_rfc2822(s).ok()?;
+                let d = t.with_timezone(&Utc).signed_duration_since(Utc::now());
+                // a date in the past means no need to wait
+                Some(d.to_std().unwrap_or_default())
             }),
         },
     ];
@@ -179,6 +198,11 @@ pub fn retry_after(resp: &Response) -> Option<Duration> {
     None
 }
 
+// backoff returns the delay before the nth retry of a transiently failed poll, which doubles from 1s up to 1min.
+pub fn backoff(retries: u32) -> Duration {
+    Duration::from_secs(1 << retries.saturating_sub(1).min(6)).min(Duration::from_secs(60))
+}
+
 pub fn is_valid_status_code(status_code: StatusCode) -> bool {
     [
         StatusCode::Ok,
@@ -0,0 +1,403 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use azure_core::error::ErrorKind;
+use azure_core::http::headers::{HeaderName, Headers};
+use azure_core::http::request::Body;
+use azure_core::http::{new_http_client, HttpClient, RawResponse, Request, StatusCode};
+use azure_core::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::client::logging::SENSITIVE_HEADERS;
+
+// The environment variables to select the recording mode ("record" or "playback"), and the cassette file.
+const RECORDING_MODE_ENV: &str = "AZURE_RS_RECORDING_MODE";
+const CASSETTE_ENV: &str = "AZURE_RS_CASSETTE";
+
+const SCRUBBED_ID: &str = "00000000-0000-0000-0000-000000000000";
+
+/// Recording selects whether the HTTP interactions of the client are recorded to, or replayed from a cassette file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recording {
+    /// Sends the requests to Azure, and records every request/response pair (including the LRO polls). The
+    /// interactions are appended to the existing cassette, so that a script of multiple commands is recorded as a
+    /// whole. Remove the cassette to record from scratch.
+    Record(PathBuf),
+    /// Serves the responses from the cassette, without touching Azure.
+    Playback(PathBuf),
+}
+
+impl Recording {
+    /// Reads the recording mode from the `AZURE_RS_RECORDING_MODE` (either `record` or `playback`) and
+    /// `AZURE_RS_CASSETTE` environment variables. It returns None if the mode is not set.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(mode) = std::env::var(RECORDING_MODE_ENV) else {
+            return Ok(None);
+        };
+        let cassette = std::env::var_os(CASSETTE_ENV)
+            .map(PathBuf::from)
+            .ok_or(Error::message(
+                ErrorKind::Other,
+                format!("{CASSETTE_ENV} must be set in the {mode} mode"),
+            ))?;
+        match mode.to_lowercase().as_str() {
+            "record" => Ok(Some(Recording::Record(cassette))),
+            "playback" => Ok(Some(Recording::Playback(cassette))),
+            _ => Err(Error::message(
+                ErrorKind::Other,
+                format!(r#"invalid {RECORDING_MODE_ENV} "{mode}", expect "record" or "playback""#),
+            )),
+        }
+    }
+
+    pub fn is_playback(&self) -> bool {
+        matches!(self, Recording::Playback(_))
+    }
+
+    // http_client returns the transport of the pipeline in this mode.
+    pub(crate) fn http_client(&self) -> Result<Arc<dyn HttpClient>> {
+        match self {
+            Recording::Record(path) => Ok(Arc::new(Recorder {
+                inner: new_http_client(),
+                path: path.clone(),
+                lock: Mutex::new(()),
+            })),
+            Recording::Playback(path) => Ok(Arc::new(Player {
+                interactions: Mutex::new(Cassette::load(path)?.interactions),
+            })),
+        }
+    }
+}
+
+/// Cassette is the list of the recorded HTTP interactions, in the order they happened.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<Value>,
+}
+
+impl Cassette {
+    // load_or_default loads the cassette, or returns an empty one if it doesn't exist yet.
+    fn load_or_default(path: &Path) -> Result<Self> {
+        match path.exists() {
+            true => Self::load(path),
+            false => Ok(Self::default()),
+        }
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(std::fs::write(path, serde_json::to_string_pretty(self)?)?)
+    }
+}
+
+// Recorder sends the requests via the inner HTTP client, and appends the scrubbed interactions to the cassette.
+#[derive(Debug)]
+struct Recorder {
+    inner: Arc<dyn HttpClient>,
+    path: PathBuf,
+    // The lock of the cassette, which is read and rewritten for each interaction.
+    lock: Mutex<()>,
+}
+
+#[async_trait::async_trait]
+impl HttpClient for Recorder {
+    async fn execute_request(&self, request: &Request) -> Result<RawResponse> {
+        let resp = self.inner.execute_request(request).await?;
+        let (status, headers, body) = resp.deconstruct();
+        let body = body.collect().await?;
+
+        let interaction = Interaction {
+            request: RecordedRequest {
+                method: request.method().to_string(),
+                url: scrub(request.url().as_str()),
+                headers: record_headers(request.headers()),
+                body: match request.body() {
+                    Body::Bytes(bytes) => record_body(bytes),
+                    _ => None,
+                },
+            },
+            response: RecordedResponse {
+                status: u16::from(status),
+                headers: record_headers(&headers),
+                body: record_body(&body),
+            },
+        };
+        {
+            let _lock = self.lock.lock().unwrap();
+            // Save as it goes, so that the interactions are kept even if the command fails halfway
+            let mut cassette = Cassette::load_or_default(&self.path)?;
+            cassette.interactions.push(interaction);
+            cassette.save(&self.path)?;
+        }
+        Ok(RawResponse::from_bytes(status, headers, body))
+    }
+}
+
+// Player serves the responses of the recorded interactions, which are matched by the method and URL in order.
+#[derive(Debug)]
+struct Player {
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+#[async_trait::async_trait]
+impl HttpClient for Player {
+    async fn execute_request(&self, request: &Request) -> Result<RawResponse> {
+        let method = request.method().to_string();
+        let url = scrub(request.url().as_str());
+        let mut interactions = self.interactions.lock().unwrap();
+        let i = interactions
+            .iter()
+            .position(|i| i.request.method == method && i.request.url == url)
+            .ok_or(Error::message(
+                ErrorKind::Io,
+                format!("no recorded interaction for {method} {url}"),
+            ))?;
+        let response = interactions.remove(i).response;
+
+        let mut headers = Headers::new();
+        for (name, value) in response.headers {
+            headers.insert(HeaderName::from(name), value);
+        }
+        // The replayed LROs are polled without delay
+        headers.insert("retry-after", "0");
+        let body = match response.body {
+            Some(Value::String(s)) => s.into_bytes(),
+            Some(value) => serde_json::to_vec(&value)?,
+            None => vec![],
+        };
+        Ok(RawResponse::from_bytes(
+            StatusCode::from(response.status),
+            headers,
+            body,
+        ))
+    }
+}
+
+fn record_headers(headers: &Headers) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .filter(|(name, _)| !SENSITIVE_HEADERS.contains(&name.as_str()))
+        .map(|(name, value)| (name.as_str().to_string(), scrub(value.as_str())))
+        .collect()
+}
+
+// record_body records the JSON body as is, which is readable in the cassette, or as a string otherwise.
+fn record_body(body: &[u8]) -> Option<Value> {
+    if body.is_empty() {
+        return None;
+    }
+    let body = scrub(&String::from_utf8_lossy(body));
+    match serde_json::from_str(&body) {
+        Ok(mut value) => {
+            scrub_id_fields(&mut value);
+            Some(value)
+        }
+        Err(_) => Some(Value::String(body)),
+    }
+}
+
+// The fields of the subscription and tenant ids, e.g. of the subscriptions listed by "GET /subscriptions".
+const ID_FIELDS: [&str; 3] = ["subscriptionId", "tenantId", "homeTenantId"];
+
+// scrub_id_fields replaces the subscription and tenant ids in the JSON value, which are not in a resource id.
+fn scrub_id_fields(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    Value::String(id)
+                        if is_guid(id) && ID_FIELDS.iter().any(|f| f.eq_ignore_ascii_case(key)) =>
+                    {
+                        *id = SCRUBBED_ID.to_string()
+                    }
+                    value => scrub_id_fields(value),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(scrub_id_fields),
+        _ => {}
+    }
+}
+
+// scrub replaces the subscription ids in the text (e.g. a URL or a resource id), which are recognized
+// as the GUID following "subscriptions/".
+fn scrub(text: &str) -> String {
+    const PREFIX: &str = "subscriptions/";
+    let lower = text.to_ascii_lowercase();
+    let mut result = String::with_capacity(text.len());
+    let mut pos = 0;
+    while let Some(i) = lower[pos..].find(PREFIX) {
+        let start = pos + i + PREFIX.len();
+        result.push_str(&text[pos..start]);
+        pos = start;
+        if let Some(id) = text.get(start..start + SCRUBBED_ID.len())
+            && is_guid(id)
+        {
+            result.push_str(SCRUBBED_ID);
+            pos += id.len();
+        }
+    }
+    result.push_str(&text[pos..]);
+    result
+}
+
+fn is_guid(s: &str) -> bool {
+    s.len() == 36
+        && s.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use azure_core::http::{Method, Url};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn scrub_subscriptions() {
+        assert_eq!(
+            scrub("https://management.azure.com/subscriptions/0b1f6471-1bf0-4dda-aec3-111122223333/resourceGroups/foo"),
+            "https://management.azure.com/subscriptions/00000000-0000-0000-0000-000000000000/resourceGroups/foo"
+        );
+        assert_eq!(
+            scrub(r#"{"id": "/Subscriptions/0B1F6471-1BF0-4DDA-AEC3-111122223333"}"#),
+            r#"{"id": "/Subscriptions/00000000-0000-0000-0000-000000000000"}"#
+        );
+        assert_eq!(
+            scrub("/subscriptions/{subscriptionId}/subscriptions/"),
+            "/subscriptions/{subscriptionId}/subscriptions/"
+        );
+        // The ids in the bare fields, e.g. of "GET /subscriptions"
+        let body = r#"{"value": [{
+            "id": "/subscriptions/0b1f6471-1bf0-4dda-aec3-111122223333",
+            "subscriptionId": "0b1f6471-1bf0-4dda-aec3-111122223333",
+            "tenantId": "72f988bf-86f1-41af-91ab-2d7cd011db47",
+            "homeTenantId": "72f988bf-86f1-41af-91ab-2d7cd011db47",
+            "managedByTenants": [{"tenantId": "2f4a9838-26b7-47ee-be60-ccc1fdec5953"}],
+            "displayName": "foo"
+        }]}"#;
+        assert_eq!(
+            record_body(body.as_bytes()),
+            Some(serde_json::json!({"value": [{
+                "id": "/subscriptions/00000000-0000-0000-0000-000000000000",
+                "subscriptionId": "00000000-0000-0000-0000-000000000000",
+                "tenantId": "00000000-0000-0000-0000-000000000000",
+                "homeTenantId": "00000000-0000-0000-0000-000000000000",
+                "managedByTenants": [{"tenantId": "00000000-0000-0000-0000-000000000000"}],
+                "displayName": "foo"
+            }]}))
+        );
+    }
+
+    #[tokio::test]
+    async fn record_and_playback() {
+        let path = std::env::temp_dir()
+            .join(uuid::Uuid::new_v4().to_string())
+            .join("cassette.json");
+        let url = "https://management.azure.com/subscriptions/0b1f6471-1bf0-4dda-aec3-111122223333/operations/1";
+
+        // Record the interactions of a mocked transport
+        #[derive(Debug)]
+        struct Mock;
+        #[async_trait::async_trait]
+        impl HttpClient for Mock {
+            async fn execute_request(&self, _: &Request) -> Result<RawResponse> {
+                let mut headers = Headers::new();
+                headers.insert("retry-after", "10");
+                headers.insert("set-cookie", "secret");
+                Ok(RawResponse::from_bytes(
+                    StatusCode::Ok,
+                    headers,
+                    r#"{"status": "InProgress"}"#,
+                ))
+            }
+        }
+        let recorder = Recorder {
+            inner: Arc::new(Mock),
+            path: path.clone(),
+            lock: Mutex::new(()),
+        };
+        let mut request = Request::new(Url::parse(url).unwrap(), Method::Get);
+        request.insert_header("authorization", "Bearer secret");
+        recorder.execute_request(&request).await.unwrap();
+
+        let cassette = Cassette::load(&path).unwrap();
+        assert_eq!(
+            cassette,
+            Cassette {
+                interactions: vec![Interaction {
+                    request: RecordedRequest {
+                        method: "GET".to_string(),
+                        url: "https://management.azure.com/subscriptions/00000000-0000-0000-0000-000000000000/operations/1".to_string(),
+                        headers: BTreeMap::new(),
+                        body: None,
+                    },
+                    response: RecordedResponse {
+                        status: 200,
+                        headers: BTreeMap::from([("retry-after".to_string(), "10".to_string())]),
+                        body: Some(serde_json::json!({"status": "InProgress"})),
+                    },
+                }]
+            }
+        );
+
+        // Replay it, which is matched regardless of the subscription id
+        let player = Recording::Playback(path.clone()).http_client().unwrap();
+        let resp = player.execute_request(&request).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::Ok);
+        assert_eq!(
+            resp.headers()
+                .get_optional_str(&HeaderName::from_static("retry-after")),
+            Some("0")
+        );
+        assert_eq!(
+            resp.into_body().collect().await.unwrap(),
+            r#"{"status":"InProgress"}"#
+        );
+        // Each interaction is served once
+        assert!(player.execute_request(&request).await.is_err());
+
+        // The recorder of a later command appends to the cassette
+        let recorder = Recorder {
+            inner: Arc::new(Mock),
+            path: path.clone(),
+            lock: Mutex::new(()),
+        };
+        recorder.execute_request(&request).await.unwrap();
+        assert_eq!(Cassette::load(&path).unwrap().interactions.len(), 2);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
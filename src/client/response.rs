@@ -3,6 +3,8 @@ use azure_core::http::{headers::Headers, RawResponse, StatusCode};
 use azure_core::{Error, Result};
 use bytes::Bytes;
 
+use crate::error::ArmError;
+
 #[derive(Debug, Clone)]
 pub struct Response {
     pub status_code: StatusCode,
@@ -20,6 +22,16 @@ impl Response {
             body,
         })
     }
+
+    // pending returns a placeholder response of an in-progress LRO, which is used when the poller is resumed
+    // from a handle, as the initial response is no longer available.
+    pub(crate) fn pending() -> Self {
+        Self {
+            status_code: StatusCode::Accepted,
+            headers: Headers::new(),
+            body: Bytes::new(),
+        }
+    }
 }
 
 impl From<Response> for ErrorKind {
@@ -28,9 +40,14 @@ impl From<Response> for ErrorKind {
     }
 }
 
+// The ARM error in the body is kept as the source of the error, which can be downcast to ArmError.
 impl From<Response> for Error {
     fn from(val: Response) -> Self {
+        let arm_error = ArmError::from_body(u16::from(val.status_code), &val.body);
         let error_kind: ErrorKind = val.into();
-        error_kind.into_error()
+        match arm_error {
+            Some(arm_error) => Error::new(error_kind, arm_error),
+            None => error_kind.into_error(),
+        }
     }
 }
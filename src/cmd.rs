@@ -1,15 +1,57 @@
-use crate::api::{metadata, ApiManager};
+use crate::api::types::ArgType;
+use crate::api::{input, metadata, shorthand, ApiManager};
 use crate::arg::CliInput;
-use clap::{command, Arg, Command};
+use crate::output::{self, OutputFormat};
+use clap::{command, value_parser, Arg, ArgAction, Command};
+use std::collections::HashSet;
 
 pub fn cmd() -> Command {
-    cmd_base().subcommand(cmd_api_stub())
+    cmd_base()
+        .subcommand(cmd_api_stub())
+        .subcommand(cmd_operation())
+        .subcommand(cmd_account())
 }
 
 fn cmd_base() -> Command {
     command!()
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .args(global_args())
+}
+
+fn global_args() -> Vec<Arg> {
+    vec![
+        Arg::new("output")
+            .short('o')
+            .long("output")
+            .value_name("format")
+            .value_parser(value_parser!(OutputFormat))
+            .default_value("json")
+            .global(true)
+            .help("Output format."),
+        Arg::new("query")
+            .long("query")
+            .value_name("JMESPath")
+            .value_parser(output::parse_query)
+            .global(true)
+            .help("JMESPath query string. See http://jmespath.org/ for more examples."),
+        Arg::new("timeout")
+            .long("timeout")
+            .value_name("seconds")
+            .value_parser(value_parser!(u64).range(..=u64::from(u32::MAX)))
+            .global(true)
+            .help("The maximum time to wait for the command to complete, including the long running operation."),
+        Arg::new("verbose")
+            .long("verbose")
+            .action(ArgAction::SetTrue)
+            .global(true)
+            .help("Log the HTTP requests and their timings to stderr."),
+        Arg::new("debug")
+            .long("debug")
+            .action(ArgAction::SetTrue)
+            .global(true)
+            .help("Log the HTTP requests with their headers and bodies to stderr, the secrets are redacted."),
+    ]
 }
 
 fn cmd_api_stub() -> Command {
@@ -22,6 +64,48 @@ fn cmd_api_stub() -> Command {
     )
 }
 
+fn cmd_operation() -> Command {
+    let id = Arg::new("id")
+        .long("id")
+        .value_name("value")
+        .required(true)
+        .help("The id of the operation, which is returned by the command run with --no-wait.");
+    Command::new("operation")
+        .about("Manage the long running operations started with --no-wait.")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommands([
+            Command::new("list").about("List the outstanding operations."),
+            Command::new("show")
+                .about("Poll the current status of an operation.")
+                .arg(id.clone()),
+            Command::new("wait")
+                .about("Wait for an operation to finish, and show its result.")
+                .arg(id),
+        ])
+}
+
+fn cmd_account() -> Command {
+    Command::new("account")
+        .about("Manage the subscription that the commands run against by default.")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommands([
+            Command::new("list").about("List the subscriptions that the identity can access."),
+            Command::new("set")
+                .about("Set the default subscription, which the commands run against when --subscription is omitted.")
+                .arg(
+                    Arg::new("subscription")
+                        .short('s')
+                        .long("subscription")
+                        .value_name("value")
+                        .required(true)
+                        .help("The name or id of the subscription."),
+                ),
+            Command::new("show").about("Show the default subscription."),
+        ])
+}
+
 fn cmd_api_base_real() -> Command {
     cmd_api_base()
         .subcommand_required(true)
@@ -32,7 +116,8 @@ pub fn cmd_api_base() -> Command {
     Command::new("api").about("Directly invoke the Azure API primitives.")
 }
 
-pub fn cmd_api(api_manager: &ApiManager, input: &CliInput) -> Command {
+// cmd_api builds the command of the API, whose subscription arguments default to the subscription, if any.
+pub fn cmd_api(api_manager: &ApiManager, input: &CliInput, subscription: Option<&str>) -> Command {
     let pos_args = input.pos_args();
 
     // No positional argument specified, list the rps
@@ -44,10 +129,9 @@ pub fn cmd_api(api_manager: &ApiManager, input: &CliInput) -> Command {
     let rp = pos_args.first().unwrap();
     match api_manager.read_metadata(rp) {
         Ok(metadata) => {
-            let mut args = pos_args.iter();
             let mut command_names = vec![];
 
-            // Construct a fake command group here to initiate the following while loop
+            // Construct a fake command group here to initiate the following loop
             let mut cg = metadata::CommandGroup {
                 name: rp.to_string(),
                 command_groups: Some(metadata.command_groups),
@@ -56,7 +140,7 @@ pub fn cmd_api(api_manager: &ApiManager, input: &CliInput) -> Command {
 
             let mut c: Option<metadata::Command> = None;
 
-            while let Some(arg) = args.next() {
+            for arg in pos_args.iter() {
                 command_names.push(arg.to_string());
 
                 if let Some(v) = cg
@@ -85,6 +169,65 @@ pub fn cmd_api(api_manager: &ApiManager, input: &CliInput) -> Command {
             if let Some(c) = c {
                 // Construct the last command name as a Command, which contains args
                 cmd = cmd.args(build_args(&c.arg_groups));
+                if c.operations.iter().any(|op| op.is_pageable()) {
+                    cmd = cmd.args(paging_args());
+                }
+                if c.instance_update().is_some() {
+                    cmd = cmd.args(generic_update_args());
+                }
+                if let Some(subscription) = subscription {
+                    // The subscription can be omitted, which defaults to the one set by "account set"
+                    let subscription_args: HashSet<&str> =
+                        c.subscription_args().map(|arg| arg.var.as_str()).collect();
+                    cmd = cmd.mut_args(|arg| {
+                        match subscription_args.contains(arg.get_id().as_str()) {
+                            // The default value doesn't satisfy the required argument for clap
+                            true => arg.default_value(subscription.to_string()).required(false),
+                            false => arg,
+                        }
+                    });
+                }
+                let id_args: HashSet<&str> = c.id_args().map(|arg| arg.var.as_str()).collect();
+                if !id_args.is_empty() {
+                    // The id parts can be specified by the resource ids of "--ids" instead
+                    cmd = cmd.arg(ids_arg()).mut_args(|arg| {
+                        if !id_args.contains(arg.get_id().as_str()) {
+                            return arg;
+                        }
+                        let arg = arg.conflicts_with("ids");
+                        match arg.is_required_set() {
+                            true => arg.required(false).required_unless_present("ids"),
+                            false => arg,
+                        }
+                    });
+                }
+                if let Some(body_args) = c.body_args() {
+                    // The arguments of the body can be specified by "--body" instead
+                    cmd = cmd.arg(body_arg()).mut_args(|arg| {
+                        if arg.is_required_set() && body_args.contains(arg.get_id().as_str()) {
+                            arg.required(false).required_unless_present("body")
+                        } else {
+                            arg
+                        }
+                    });
+                }
+                if c.long_running() {
+                    cmd = cmd.arg(
+                        Arg::new("no-wait")
+                            .long("no-wait")
+                            .action(ArgAction::SetTrue)
+                            .help("Do not wait for the long running operation to finish. Use \"operation wait\" to wait for it later."),
+                    );
+                }
+                if c.confirmation.as_ref().is_some_and(|msg| !msg.is_empty()) {
+                    cmd = cmd.arg(
+                        Arg::new("yes")
+                            .short('y')
+                            .long("yes")
+                            .action(ArgAction::SetTrue)
+                            .help("Do not prompt for confirmation."),
+                    );
+                }
             } else {
                 // Construct the last command name as a CommandGroup, which contains commands and potential
                 // command groups
@@ -111,7 +254,7 @@ pub fn cmd_api(api_manager: &ApiManager, input: &CliInput) -> Command {
     }
 }
 
-fn build_args(arg_groups: &Vec<metadata::ArgGroup>) -> Vec<Arg> {
+fn build_args(arg_groups: &[metadata::ArgGroup]) -> Vec<Arg> {
     let mut out = vec![];
     arg_groups
         .iter()
@@ -119,6 +262,78 @@ fn build_args(arg_groups: &Vec<metadata::ArgGroup>) -> Vec<Arg> {
     out
 }
 
+fn paging_args() -> Vec<Arg> {
+    vec![
+        Arg::new("max-items")
+            .long("max-items")
+            .value_name("value")
+            .value_parser(value_parser!(usize))
+            .help_heading("Pagination Arguments")
+            .help("Total number of items to return in the command's output. If more items are available, a token is provided to resume pagination via --next-token."),
+        Arg::new("next-token")
+            .long("next-token")
+            .value_name("value")
+            .help_heading("Pagination Arguments")
+            .help("Token to specify where to start paginating. This is the token value from a previously truncated response."),
+    ]
+}
+
+fn generic_update_args() -> Vec<Arg> {
+    vec![
+        Arg::new("set")
+            .long("set")
+            .value_name("KEY=VALUE")
+            .num_args(1..)
+            .action(ArgAction::Append)
+            .help_heading("Generic Update Arguments")
+            .help("Update an object by specifying a property path and value to set. Example: --set property1.property2=<value>."),
+        Arg::new("add")
+            .long("add")
+            .value_name("LIST KEY=VALUE")
+            .num_args(1..)
+            .action(ArgAction::Append)
+            .help_heading("Generic Update Arguments")
+            .help("Add an object to a list of objects by specifying a path and key value pairs. Example: --add property.listProperty <key=value, string or JSON string>."),
+        Arg::new("remove")
+            .long("remove")
+            .value_name("LIST INDEX")
+            .num_args(1..=2)
+            .action(ArgAction::Append)
+            .help_heading("Generic Update Arguments")
+            .help("Remove a property or an element from a list. Example: --remove property.list <index> OR --remove propertyToRemove."),
+        Arg::new("force-string")
+            .long("force-string")
+            .action(ArgAction::SetTrue)
+            .help_heading("Generic Update Arguments")
+            .help("When using 'set' or 'add', preserve string literals instead of attempting to convert to JSON."),
+    ]
+}
+
+fn ids_arg() -> Arg {
+    Arg::new("ids")
+        .long("ids")
+        .value_name("value")
+        .num_args(1..)
+        .action(ArgAction::Append)
+        .help_heading("Resource Id Arguments")
+        .help("One or more resource IDs (space-delimited), which the command runs for each. If provided, no other 'Resource Id' arguments should be specified.")
+}
+
+fn body_arg() -> Arg {
+    Arg::new("body")
+        .long("body")
+        .value_name("value")
+        .value_parser(|raw: &str| -> anyhow::Result<String> {
+            let body = input::load(raw)?;
+            match shorthand::parse(&body)? {
+                serde_json::Value::Object(_) => Ok(body),
+                _ => anyhow::bail!(r#"invalid value "{raw}", expect an object"#),
+            }
+        })
+        .help_heading("Body Arguments")
+        .help("The request body as JSON, or read from a file via @<file> (JSON or YAML). The other arguments are merged into it.")
+}
+
 fn build_arg(arg: &metadata::Arg) -> Arg {
     // The options of one argument can have 0/N short, 0/N long.
     // We reagard the first short(prefered)/long as the name.
@@ -158,7 +373,32 @@ fn build_arg(arg: &metadata::Arg) -> Arg {
     if let Some(help) = &arg.help {
         out = out.help(help.short.clone());
     }
-    out
+    let type_ = ArgType::from(arg.type_.as_str());
+    if type_ == ArgType::SubscriptionId {
+        let help = arg
+            .help
+            .as_ref()
+            .map(|help| help.short.as_str())
+            .unwrap_or_default();
+        out = out.help(
+            format!(r#"{help} Defaults to the subscription set by "account set"."#)
+                .trim_start()
+                .to_string(),
+        );
+    }
+    if type_.is_flag() {
+        out = out.num_args(0..=1).default_missing_value("true");
+    }
+    // An object takes multiple values as well, e.g. "--tags env=dev team=core"
+    if type_.is_array() || type_ == ArgType::Object {
+        out = out.num_args(1..).action(ArgAction::Append);
+    }
+    // The value can be read from a file via "@<file>", or stdin via "@-". The value of an invalid type is kept as is,
+    // which is reported by the validation along with the other violations.
+    out.value_parser(move |raw: &str| -> anyhow::Result<String> {
+        let value = input::load(raw)?;
+        Ok(type_.normalize(&value).unwrap_or(value))
+    })
 }
 
 #[test]
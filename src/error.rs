@@ -0,0 +1,418 @@
+use azure_core::error::ErrorKind;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// The command requires the user to confirm before it runs, which can't be prompted interactively.
+/// Rerun the command with `--yes` to confirm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmationRequired {
+    pub message: String,
+}
+
+impl fmt::Display for ConfirmationRequired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} Confirmation is required but the input is not interactive, use --yes to confirm.",
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for ConfirmationRequired {}
+
+/// The command is interrupted before it completes, as it is cancelled or has timed out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interrupted {
+    pub reason: InterruptReason,
+    // The last known status of the long running operation in progress, if any.
+    pub status: Option<String>,
+    // The id of the operation, with which the long running operation can be resumed by `azure operation wait`.
+    pub id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InterruptReason {
+    Cancelled,
+    TimedOut,
+}
+
+impl Interrupted {
+    pub fn new(reason: InterruptReason) -> Self {
+        Self {
+            reason,
+            status: None,
+            id: None,
+        }
+    }
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            InterruptReason::Cancelled => write!(f, "The command was cancelled.")?,
+            InterruptReason::TimedOut => write!(f, "The command timed out.")?,
+        }
+        if let Some(status) = &self.status {
+            write!(
+                f,
+                r#" The last known status of the operation is "{status}"."#
+            )?;
+        }
+        if let Some(id) = &self.id {
+            write!(f, " Run `azure operation wait --id {id}` to wait for it.")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// The arguments violate the constraints declared by the API (e.g. the pattern or the allowed values), which are
+/// checked before sending any request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidArguments {
+    pub violations: Vec<Violation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// The option of the argument, e.g. `--resource-group`.
+    pub option: String,
+    pub message: String,
+}
+
+impl fmt::Display for InvalidArguments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid arguments:")?;
+        for violation in &self.violations {
+            write!(f, "\n  {}: {}", violation.option, violation.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidArguments {}
+
+/// The user input can't be applied to build the requests, e.g. a property path of `--set` that is not in the
+/// instance, or a missing required parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidInput {
+    pub message: String,
+}
+
+impl From<anyhow::Error> for InvalidInput {
+    fn from(e: anyhow::Error) -> Self {
+        Self {
+            message: e.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for InvalidInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for InvalidInput {}
+
+/// ArmError is the error response of ARM, or the error of a failed long running operation, which follows the
+/// [ARM error format](https://github.com/Azure/azure-resource-manager-rpc/blob/master/v1.0/common-api-details.md#error-response-content).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArmError {
+    /// The HTTP status code of the response.
+    pub status: u16,
+    pub error: ErrorDetail,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorDetail {
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<ErrorDetail>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_info: Vec<AdditionalInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdditionalInfo {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub info: Value,
+}
+
+impl ArmError {
+    /// Parses the error from the response body, which is either `{"error": {...}}` (including the status of a failed
+    /// long running operation), `{"odata.error": {...}}`, or the error itself. It returns None if the body is not an
+    /// error.
+    pub fn from_body(status: u16, body: &[u8]) -> Option<Self> {
+        let body: Value = serde_json::from_slice(body).ok()?;
+        let error = ["error", "odata.error"]
+            .iter()
+            .find_map(|name| body.get(name))
+            .unwrap_or(&body);
+        Some(Self {
+            status,
+            error: ErrorDetail::from_value(error)?,
+        })
+    }
+}
+
+impl ErrorDetail {
+    // from_value parses the error leniently, e.g. the OData message can be `{"lang": "en-US", "value": "..."}`,
+    // and the details or additional info that are not well-formed are skipped.
+    fn from_value(value: &Value) -> Option<Self> {
+        let code = value.get("code")?.as_str()?.to_string();
+        let message = match value.get("message") {
+            Some(Value::String(message)) => message.clone(),
+            Some(message) => message
+                .get("value")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            None => String::new(),
+        };
+        let list = |name| {
+            value
+                .get(name)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+        };
+        Some(Self {
+            code,
+            message,
+            target: value
+                .get("target")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            details: list("details").filter_map(Self::from_value).collect(),
+            additional_info: list("additionalInfo")
+                .filter_map(|info| serde_json::from_value(info.clone()).ok())
+                .collect(),
+        })
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let pad = " ".repeat(indent);
+        write!(f, "{pad}({}) {}", self.code, self.message)?;
+        if let Some(target) = &self.target {
+            write!(f, "\n{pad}Target: {target}")?;
+        }
+        for info in &self.additional_info {
+            write!(f, "\n{pad}Additional info ({}): {}", info.type_, info.info)?;
+        }
+        if !self.details.is_empty() {
+            write!(f, "\n{pad}Details:")?;
+            for detail in &self.details {
+                writeln!(f)?;
+                detail.write(f, indent + 2)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ArmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.write(f, 0)?;
+        write!(f, "\nStatus: {}", self.status)
+    }
+}
+
+impl std::error::Error for ArmError {}
+
+/// ErrorCategory classifies the error of a command, which decides the exit code of the process:
+///
+/// | Code | Category |
+/// |------|----------|
+/// | 0    | Success |
+/// | 1    | Any other error |
+/// | 2    | Usage error, e.g. an invalid argument |
+/// | 3    | The resource is not found |
+/// | 4    | Authentication or authorization failure |
+/// | 5    | The request is rejected by the service as invalid, e.g. a bad request or conflict |
+/// | 6    | The request is throttled |
+/// | 7    | Service error, including a failed long running operation |
+/// | 124  | The command timed out |
+/// | 130  | The command was cancelled |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    General,
+    Usage,
+    NotFound,
+    Auth,
+    Validation,
+    Throttled,
+    Service,
+    TimedOut,
+    Cancelled,
+}
+
+impl ErrorCategory {
+    /// Classifies the error returned by [`crate::run`], which can be any error of the client or the poller.
+    pub fn of(e: &anyhow::Error) -> Self {
+        if e.is::<clap::Error>()
+            || e.is::<ConfirmationRequired>()
+            || e.is::<InvalidArguments>()
+            || e.is::<InvalidInput>()
+        {
+            return ErrorCategory::Usage;
+        }
+        if let Some(interrupted) = e.downcast_ref::<Interrupted>() {
+            return match interrupted.reason {
+                InterruptReason::Cancelled => ErrorCategory::Cancelled,
+                InterruptReason::TimedOut => ErrorCategory::TimedOut,
+            };
+        }
+        if let Some(arm_error) = e.downcast_ref::<ArmError>() {
+            return Self::of_status(arm_error.status);
+        }
+        let Some(azure_error) = e.downcast_ref::<azure_core::Error>() else {
+            return ErrorCategory::General;
+        };
+        match azure_error.kind() {
+            ErrorKind::HttpResponse { status, .. } => Self::of_status(u16::from(*status)),
+            ErrorKind::Credential => ErrorCategory::Auth,
+            _ => ErrorCategory::General,
+        }
+    }
+
+    // of_status classifies the HTTP status of the error response. A successful status means the long running
+    // operation has failed, as reported by its status.
+    fn of_status(status: u16) -> Self {
+        match status {
+            401 | 403 => ErrorCategory::Auth,
+            404 => ErrorCategory::NotFound,
+            429 => ErrorCategory::Throttled,
+            400..=499 => ErrorCategory::Validation,
+            _ => ErrorCategory::Service,
+        }
+    }
+
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorCategory::General => 1,
+            ErrorCategory::Usage => 2,
+            ErrorCategory::NotFound => 3,
+            ErrorCategory::Auth => 4,
+            ErrorCategory::Validation => 5,
+            ErrorCategory::Throttled => 6,
+            ErrorCategory::Service => 7,
+            ErrorCategory::TimedOut => 124,
+            ErrorCategory::Cancelled => 130,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn arm_error() {
+        let body = json!({
+            "status": "Failed",
+            "error": {
+                "code": "InvalidTemplateDeployment",
+                "message": "The template deployment failed.",
+                "details": [{
+                    "code": "RequestDisallowedByPolicy",
+                    "target": "vnet",
+                    "message": "Resource 'vnet' was disallowed by policy.",
+                    "additionalInfo": [{"type": "PolicyViolation", "info": {"policyDefinitionName": "foo"}}]
+                }, "not an error"]
+            }
+        });
+        let error = ArmError::from_body(200, body.to_string().as_bytes()).unwrap();
+        assert_eq!(error.error.details.len(), 1);
+        assert_eq!(
+            error.to_string(),
+            r#"(InvalidTemplateDeployment) The template deployment failed.
+Details:
+  (RequestDisallowedByPolicy) Resource 'vnet' was disallowed by policy.
+  Target: vnet
+  Additional info (PolicyViolation): {"policyDefinitionName":"foo"}
+Status: 200"#
+        );
+
+        let body = json!({"odata.error": {"code": "ResourceNotFound", "message": {"lang": "en-US", "value": "Not found."}}});
+        assert_eq!(
+            ArmError::from_body(404, body.to_string().as_bytes()),
+            Some(ArmError {
+                status: 404,
+                error: ErrorDetail {
+                    code: "ResourceNotFound".to_string(),
+                    message: "Not found.".to_string(),
+                    target: None,
+                    details: vec![],
+                    additional_info: vec![],
+                }
+            })
+        );
+
+        assert_eq!(ArmError::from_body(500, b"Internal Server Error"), None);
+        assert_eq!(ArmError::from_body(200, br#"{"status": "Failed"}"#), None);
+    }
+
+    #[test]
+    fn categories() {
+        let arm_error = |status| ArmError {
+            status,
+            error: ErrorDetail {
+                code: "Foo".to_string(),
+                message: "foo".to_string(),
+                target: None,
+                details: vec![],
+                additional_info: vec![],
+            },
+        };
+        let cases: Vec<(anyhow::Error, ErrorCategory)> = vec![
+            (anyhow::anyhow!("foo"), ErrorCategory::General),
+            (
+                clap::Error::new(clap::error::ErrorKind::InvalidValue).into(),
+                ErrorCategory::Usage,
+            ),
+            (
+                InvalidArguments { violations: vec![] }.into(),
+                ErrorCategory::Usage,
+            ),
+            (
+                InvalidInput::from(anyhow::anyhow!("invalid property path")).into(),
+                ErrorCategory::Usage,
+            ),
+            (
+                Interrupted::new(InterruptReason::TimedOut).into(),
+                ErrorCategory::TimedOut,
+            ),
+            (arm_error(404).into(), ErrorCategory::NotFound),
+            (arm_error(409).into(), ErrorCategory::Validation),
+            (arm_error(200).into(), ErrorCategory::Service),
+            (
+                azure_core::Error::from(ErrorKind::http_response(
+                    azure_core::http::StatusCode::TooManyRequests,
+                    None,
+                ))
+                .into(),
+                ErrorCategory::Throttled,
+            ),
+            (
+                azure_core::Error::message(ErrorKind::Credential, "no credential").into(),
+                ErrorCategory::Auth,
+            ),
+        ];
+        for (e, category) in cases {
+            assert_eq!(ErrorCategory::of(&e), category, "{e}");
+        }
+        assert_eq!(ErrorCategory::Cancelled.exit_code(), 130);
+    }
+}
@@ -1,44 +1,288 @@
 use anyhow::Result;
 use api::ApiManager;
 use arg::CliInput;
+use azure_core::http::Context;
 use clap::{ArgMatches, Command};
-use client::Client;
+use client::{Client, Deadline, HttpLogger, LogLevel};
+use output::OutputFormat;
 use std::path::PathBuf;
+use std::time::Duration;
 
+pub mod account;
 pub mod api;
 pub mod arg;
 pub mod azidentityext;
 pub mod client;
 pub mod cmd;
+pub mod error;
+pub mod operation;
+pub mod output;
+pub mod store;
 
 #[cfg(target_arch = "wasm32")]
 pub mod wasm_exports;
 
-pub async fn run(p: PathBuf, client: &Client, raw_input: Vec<String>) -> Result<String> {
+pub async fn run(
+    p: PathBuf,
+    client: &Client,
+    raw_input: Vec<String>,
+    ctx: &Context<'_>,
+) -> Result<String> {
     let api_manager = ApiManager::new(p)?;
 
     let matches = get_matches(cmd::cmd(), raw_input.clone())?;
 
-    match matches.subcommand() {
+    let (res, schema, matches) = match matches.subcommand() {
         Some(("api", matches)) => {
-            let args = if let Some(args) = matches.get_many::<String>("args") {
-                args.cloned().collect()
-            } else {
-                vec![]
+            // The command is resolved by the leading positional arguments, while the options that follow, including
+            // the multi-value ones (e.g. "--tags env=dev team=core"), are left to clap
+            let args: Vec<String> = match matches.get_many::<String>("args") {
+                Some(args) => args
+                    .take_while(|arg| !arg.starts_with('-'))
+                    .cloned()
+                    .collect(),
+                None => vec![],
             };
             let input = CliInput::new(args)?;
-            let mut matches = get_matches(cmd::cmd_api(&api_manager, &input), raw_input.clone())?;
+            let account = default_account();
+            let subscription = account.as_ref().map(|account| account.id.as_str());
+            let cmd = cmd::cmd_api(&api_manager, &input, subscription);
+            let matches = leaf_matches(get_matches(cmd.clone(), raw_input.clone())?);
+
+            // With "--ids", the command runs once for each resource id, whose parts fill in the arguments
+            let runs = match matches.get_many::<String>("ids") {
+                Some(ids) => {
+                    let command = api_manager.resolve_command(&input)?;
+                    let mut runs = vec![];
+                    for id in ids {
+                        let id_input = id_input(&raw_input, &command, id)?;
+                        let matches = leaf_matches(get_matches(cmd.clone(), id_input.clone())?);
+                        runs.push((id_input, matches));
+                    }
+                    runs
+                }
+                None => vec![(raw_input.clone(), matches.clone())],
+            };
+            let mut invokers = vec![];
+            for (raw_input, matches) in &runs {
+                invokers.push(
+                    api_manager
+                        .build_invocation(&input, matches)?
+                        .with_command_line(command_line(raw_input)),
+                );
+            }
 
             // Invoke the api call
-            while let Some((_, m)) = matches.subcommand() {
-                matches = m.clone();
+            if let Some(message) = invokers[0].confirmation()
+                && matches.try_get_one::<bool>("yes").ok().flatten() != Some(&true)
+            {
+                confirm(message)?;
             }
-            let invoker = api_manager.build_invocation(&input, &matches)?;
-            let res = invoker.invoke(&client).await?;
-            return Ok(res);
+            let res = timed(&command_context(ctx, &matches), |ctx| async {
+                let mut results = vec![];
+                for invoker in &invokers {
+                    results.push(invoker.invoke(client, ctx).await?);
+                }
+                if results.len() == 1 {
+                    return Ok(results.pop().flatten());
+                }
+                // The commands without output, e.g. delete, stay silent
+                let results: Vec<_> = results.into_iter().flatten().collect();
+                Ok((!results.is_empty()).then(|| results.into()))
+            })
+            .await
+            .map_err(normalize)?;
+            (res, invokers[0].output_schema(), matches)
+        }
+        Some(("operation", matches)) => {
+            let leaf = leaf_matches(matches.clone());
+            let res = timed(&command_context(ctx, &leaf), |ctx| {
+                operation::run(client, ctx, matches)
+            })
+            .await
+            .map_err(normalize)?;
+            (res, None, leaf)
+        }
+        Some(("account", matches)) => {
+            let leaf = leaf_matches(matches.clone());
+            let res = timed(&command_context(ctx, &leaf), |ctx| {
+                account::run(client, ctx, matches)
+            })
+            .await
+            .map_err(normalize)?;
+            (res, None, leaf)
         }
         _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
+    };
+
+    let format = matches
+        .get_one::<OutputFormat>("output")
+        .copied()
+        .unwrap_or_default();
+    match matches.get_one::<String>("query") {
+        Some(expr) => {
+            // The schema no longer describes the result once it is queried
+            let res = output::query(res.as_ref(), expr)?;
+            output::render(res.as_ref(), format, None)
+        }
+        None => output::render(res.as_ref(), format, schema.as_ref()),
+    }
+}
+
+// leaf_matches returns the matches of the innermost subcommand, i.e. the command to run.
+fn leaf_matches(mut matches: ArgMatches) -> ArgMatches {
+    while let Some((_, m)) = matches.subcommand() {
+        matches = m.clone();
+    }
+    matches
+}
+
+// id_input returns the CLI input that runs the command for the resource id, where "--ids" is replaced by the
+// arguments of the id parts.
+fn id_input(
+    raw_input: &[String],
+    command: &api::metadata::Command,
+    id: &str,
+) -> Result<Vec<String>> {
+    let mut input = vec![];
+    let mut in_ids = false;
+    for arg in raw_input {
+        if arg == "--ids" {
+            in_ids = true;
+            continue;
+        }
+        if in_ids && !arg.starts_with('-') {
+            continue;
+        }
+        in_ids = false;
+        if !arg.starts_with("--ids=") {
+            input.push(arg.clone());
+        }
+    }
+    for (arg, value) in command.parse_id(id)? {
+        input.extend([arg.option_name(), value]);
+    }
+    Ok(input)
+}
+
+// default_account returns the account set by "account set". It only provides the default subscription, so the
+// failure to read it is warned instead of failing the command.
+fn default_account() -> Option<store::Account> {
+    match store::Store::open().and_then(|store| store.account()) {
+        Ok(account) => account,
+        Err(e) => {
+            warn(&format!("Ignored the default subscription: {e:#}"));
+            None
+        }
+    }
+}
+
+// command_context returns the context of the command, with the deadline as specified by "--timeout", and the HTTP
+// logger as enabled by "--verbose" or "--debug".
+fn command_context<'a>(ctx: &'a Context<'_>, matches: &ArgMatches) -> Context<'a> {
+    let mut ctx = Context::with_context(ctx);
+    if let Some(timeout) = matches.try_get_one::<u64>("timeout").ok().flatten() {
+        ctx = ctx.with_value(Deadline::after(Duration::from_secs(*timeout)));
+    }
+    let flag = |name| matches.try_get_one::<bool>(name).ok().flatten() == Some(&true);
+    let level = match (flag("debug"), flag("verbose")) {
+        (true, _) => Some(LogLevel::Debug),
+        (false, true) => Some(LogLevel::Verbose),
+        _ => None,
+    };
+    match level {
+        Some(level) => ctx.with_value(HttpLogger::new(level, debug)),
+        None => ctx,
+    }
+}
+
+// timed runs the command, and logs the time it takes if the HTTP logger is enabled.
+async fn timed<'a, T, F: Future<Output = T>>(
+    ctx: &'a Context<'a>,
+    command: impl FnOnce(&'a Context<'a>) -> F,
+) -> T {
+    let start = chrono::Utc::now();
+    let res = command(ctx).await;
+    if let Some(logger) = ctx.value::<HttpLogger>() {
+        let elapsed = (chrono::Utc::now() - start).num_milliseconds();
+        logger.log(&format!("Command ran in {elapsed}ms"));
+    }
+    res
+}
+
+// normalize unwraps the interruption of the command, or the ARM error from the client error, so that the callers
+// can tell it.
+fn normalize(e: anyhow::Error) -> anyhow::Error {
+    let Some(azure_error) = e.downcast_ref::<azure_core::Error>() else {
+        return e;
+    };
+    if let Some(interrupted) = client::interrupted(azure_error) {
+        return interrupted.clone().into();
+    }
+    match azure_error.downcast_ref::<error::ArmError>() {
+        Some(arm_error) => arm_error.clone().into(),
+        None => e,
+    }
+}
+
+// command_line formats the CLI input for display, with the program name normalized.
+fn command_line(raw_input: &[String]) -> String {
+    std::iter::once("azure")
+        .chain(raw_input.iter().skip(1).map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// warn reports a message to the user without polluting the command output.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn warn(msg: &str) {
+    eprintln!("WARNING: {msg}");
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn warn(msg: &str) {
+    wasm_exports::warn(msg);
+}
+
+// debug reports the diagnostic message, e.g. the HTTP log.
+#[cfg(not(target_arch = "wasm32"))]
+fn debug(msg: &str) {
+    eprintln!("DEBUG: {msg}");
+}
+
+#[cfg(target_arch = "wasm32")]
+fn debug(msg: &str) {
+    wasm_exports::log(msg);
+}
+
+// confirm prompts the user to confirm the command, which fails if the input is not interactive.
+#[cfg(not(target_arch = "wasm32"))]
+fn confirm(message: &str) -> Result<()> {
+    use anyhow::bail;
+    use std::io::{self, IsTerminal, Write};
+    if !io::stdin().is_terminal() {
+        return Err(error::ConfirmationRequired {
+            message: message.to_string(),
+        }
+        .into());
+    }
+    eprint!("{message} (y/n): ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => bail!("Operation cancelled."),
+    }
+}
+
+// confirm can't prompt in the browser, the caller shall confirm with the user and rerun with "--yes".
+#[cfg(target_arch = "wasm32")]
+fn confirm(message: &str) -> Result<()> {
+    Err(error::ConfirmationRequired {
+        message: message.to_string(),
     }
+    .into())
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -50,7 +294,9 @@ pub fn get_matches(cmd: Command, input: Vec<String>) -> Result<ArgMatches> {
         .map_err(|e| anyhow!("{}", e.render().ansi()))
 }
 
+// get_matches returns the clap::Error as is, including the help or version to display, which the caller shall
+// print and exit with.
 #[cfg(not(target_arch = "wasm32"))]
 pub fn get_matches(cmd: Command, input: Vec<String>) -> Result<ArgMatches> {
-    Ok(cmd.get_matches_from(input))
+    Ok(cmd.try_get_matches_from(input)?)
 }
@@ -0,0 +1,64 @@
+use anyhow::Result;
+use azure_core::http::Context;
+use clap::ArgMatches;
+use serde_json::Value;
+
+use crate::client::{interrupted, Client};
+use crate::error::Interrupted;
+use crate::store::Store;
+
+/// Runs the `operation` commands, which manage the long running operations started with `--no-wait`.
+pub async fn run(
+    client: &Client,
+    ctx: &Context<'_>,
+    matches: &ArgMatches,
+) -> Result<Option<Value>> {
+    let store = Store::open()?;
+    match matches.subcommand() {
+        Some(("list", _)) => Ok(Some(serde_json::to_value(store.jobs()?)?)),
+        Some(("show", matches)) => {
+            let mut job = store.job(id(matches))?;
+            let mut poller = client.resume(&job.handle)?;
+            poller.poll(ctx).await?;
+            job.status = poller.status();
+            store.put_job(job.clone())?;
+            Ok(Some(serde_json::to_value(job)?))
+        }
+        Some(("wait", matches)) => {
+            let mut job = store.job(id(matches))?;
+            let mut poller = client.resume(&job.handle)?;
+            let res = match client.wait(ctx, &mut poller).await {
+                Ok(res) => res,
+                Err(e) => match interrupted(&e).cloned() {
+                    // The operation is still outstanding, which can be waited again
+                    Some(interrupted) => {
+                        job.status = poller.status();
+                        store.put_job(job.clone())?;
+                        return Err(Interrupted {
+                            status: Some(job.status),
+                            id: Some(job.id),
+                            ..interrupted
+                        }
+                        .into());
+                    }
+                    // The operation reaches a terminal state, it is no longer outstanding
+                    None => {
+                        store.remove_job(&job.id)?;
+                        return Err(e.into());
+                    }
+                },
+            };
+            store.remove_job(&job.id)?;
+            // The body of a deleted resource is the status of the operation, there is nothing to show
+            if res.body.is_empty() || job.handle.method.eq_ignore_ascii_case("DELETE") {
+                return Ok(None);
+            }
+            Ok(Some(serde_json::from_slice(&res.body)?))
+        }
+        _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
+    }
+}
+
+fn id(matches: &ArgMatches) -> &str {
+    matches.get_one::<String>("id").expect("the id is required")
+}
@@ -0,0 +1,314 @@
+use crate::api::metadata::Schema;
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Pretty printed JSON.
+    #[default]
+    Json,
+    /// Colorized JSON.
+    Jsonc,
+    /// YAML.
+    Yaml,
+    /// Human readable table.
+    Table,
+    /// Tab separated values, with no header.
+    Tsv,
+    /// No output.
+    None,
+}
+
+/// Renders the command result in the specified format.
+///
+/// The schema is the response schema declared in the metadata, which is used to decide the columns
+/// of the table/tsv format. If it is absent, the columns are derived from the result itself.
+pub fn render(
+    value: Option<&Value>,
+    format: OutputFormat,
+    schema: Option<&Schema>,
+) -> Result<String> {
+    let Some(value) = value else {
+        return Ok(String::new());
+    };
+    let out = match format {
+        OutputFormat::Json => serde_json::to_string_pretty(value)?,
+        OutputFormat::Jsonc => {
+            let mut out = String::new();
+            write_jsonc(&mut out, value, 0);
+            out
+        }
+        OutputFormat::Yaml => serde_yaml::to_string(value)?.trim_end().to_string(),
+        OutputFormat::Table => render_table(value, schema),
+        OutputFormat::Tsv => render_tsv(value, schema),
+        OutputFormat::None => String::new(),
+    };
+    Ok(out)
+}
+
+/// Validates a JMESPath expression, used as the value parser of the `--query` argument.
+pub fn parse_query(expr: &str) -> Result<String, String> {
+    jmespath::compile(expr)
+        .map(|_| expr.to_string())
+        .map_err(|e| format!("invalid JMESPath query: {e}"))
+}
+
+/// Evaluates the JMESPath expression against the command result.
+/// A query that evaluates to `null` results in no output.
+pub fn query(value: Option<&Value>, expr: &str) -> Result<Option<Value>> {
+    let expr = jmespath::compile(expr).map_err(|e| anyhow!("invalid JMESPath query: {e}"))?;
+    let data = value.cloned().unwrap_or(Value::Null);
+    let result = expr
+        .search(&data)
+        .map_err(|e| anyhow!("evaluating JMESPath query: {e}"))?;
+    match serde_json::to_value(&*result)? {
+        Value::Null => Ok(None),
+        v => Ok(Some(v)),
+    }
+}
+
+const COLOR_KEY: &str = "\x1b[94m";
+const COLOR_STRING: &str = "\x1b[32m";
+const COLOR_LITERAL: &str = "\x1b[36m";
+const COLOR_RESET: &str = "\x1b[0m";
+
+fn write_jsonc(out: &mut String, value: &Value, indent: usize) {
+    let pad = |n: usize| "  ".repeat(n);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push_str("{\n");
+            for (i, (k, v)) in map.iter().enumerate() {
+                out.push_str(&pad(indent + 1));
+                out.push_str(&format!(
+                    "{COLOR_KEY}{}{COLOR_RESET}: ",
+                    Value::from(k.as_str())
+                ));
+                write_jsonc(out, v, indent + 1);
+                if i + 1 != map.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            out.push_str(&pad(indent));
+            out.push('}');
+        }
+        Value::Array(arr) if !arr.is_empty() => {
+            out.push_str("[\n");
+            for (i, v) in arr.iter().enumerate() {
+                out.push_str(&pad(indent + 1));
+                write_jsonc(out, v, indent + 1);
+                if i + 1 != arr.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            out.push_str(&pad(indent));
+            out.push(']');
+        }
+        Value::String(_) => out.push_str(&format!("{COLOR_STRING}{value}{COLOR_RESET}")),
+        Value::Object(_) | Value::Array(_) => out.push_str(&value.to_string()),
+        _ => out.push_str(&format!("{COLOR_LITERAL}{value}{COLOR_RESET}")),
+    }
+}
+
+// rows returns the records to be rendered by the table/tsv format, together with the schema of each record.
+// A list result (i.e. an object with a "value" array) is flattened to its items.
+fn rows<'a>(value: &'a Value, schema: Option<&'a Schema>) -> (Vec<&'a Value>, Option<&'a Schema>) {
+    match value {
+        Value::Array(arr) => (arr.iter().collect(), schema.and_then(|s| s.item.as_deref())),
+        Value::Object(map) if map.get("value").is_some_and(Value::is_array) => {
+            let items = map["value"].as_array().unwrap();
+            let item_schema = schema
+                .and_then(|s| s.props.as_ref())
+                .and_then(|props| props.iter().find(|p| p.name.as_deref() == Some("value")))
+                .and_then(|p| p.item.as_deref());
+            (items.iter().collect(), item_schema)
+        }
+        _ => (vec![value], schema),
+    }
+}
+
+fn is_scalar_type(type_: &str) -> bool {
+    !(type_ == "object" || type_.starts_with("array") || type_.starts_with('@'))
+}
+
+// columns returns the column names of the records.
+// The scalar properties declared in the schema are preferred, as they are stable per command.
+// Otherwise, the columns are the union of the scalar fields among the records.
+fn columns(rows: &[&Value], schema: Option<&Schema>) -> Vec<String> {
+    if let Some(props) = schema.and_then(|s| s.props.as_ref()) {
+        let cols: Vec<String> = props
+            .iter()
+            .filter(|p| is_scalar_type(&p.type_) && p.additional_props.is_none())
+            .filter_map(|p| p.name.clone())
+            .collect();
+        if !cols.is_empty() {
+            return cols;
+        }
+    }
+    let mut cols: Vec<String> = vec![];
+    for row in rows {
+        if let Value::Object(map) = row {
+            for (k, v) in map {
+                if !v.is_object() && !v.is_array() && !cols.contains(k) {
+                    cols.push(k.clone());
+                }
+            }
+        }
+    }
+    cols
+}
+
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => v.to_string(),
+    }
+}
+
+fn records(value: &Value, schema: Option<&Schema>) -> (Vec<String>, Vec<Vec<String>>) {
+    let (rows, schema) = rows(value, schema);
+    if rows.iter().all(|r| !r.is_object()) {
+        let cells = rows.iter().map(|r| vec![cell(Some(r))]).collect();
+        return (vec!["Result".to_string()], cells);
+    }
+    let cols = columns(&rows, schema);
+    let cells = rows
+        .iter()
+        .map(|r| cols.iter().map(|c| cell(r.get(c))).collect())
+        .collect();
+    (cols, cells)
+}
+
+fn header(col: &str) -> String {
+    let mut chars = col.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn render_table(value: &Value, schema: Option<&Schema>) -> String {
+    let (cols, cells) = records(value, schema);
+    let headers: Vec<String> = cols.iter().map(|c| header(c)).collect();
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            cells
+                .iter()
+                .map(|r| r[i].chars().count())
+                .chain([h.chars().count()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |fields: Vec<String>| {
+        fields
+            .iter()
+            .zip(&widths)
+            .map(|(f, w)| format!("{f:<w$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let mut lines = vec![
+        line(headers),
+        line(widths.iter().map(|w| "-".repeat(*w)).collect()),
+    ];
+    lines.extend(cells.into_iter().map(line));
+    lines.join("\n")
+}
+
+fn render_tsv(value: &Value, schema: Option<&Schema>) -> String {
+    let (_, cells) = records(value, schema);
+    cells
+        .iter()
+        .map(|r| r.join("\t"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn schema() -> Schema {
+        serde_json::from_value(json!({
+            "type": "object",
+            "props": [
+                {"type": "ResourceId", "name": "id"},
+                {"type": "ResourceLocation", "name": "location"},
+                {"type": "string", "name": "name"},
+                {"type": "object", "name": "properties", "props": [{"type": "string", "name": "provisioningState"}]},
+                {"type": "object", "name": "tags", "additionalProps": {"item": {"type": "string"}}},
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn render_none() {
+        assert_eq!(render(None, OutputFormat::Json, None).unwrap(), "");
+        assert_eq!(
+            render(Some(&json!({"a": 1})), OutputFormat::None, None).unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn render_table_with_schema() {
+        let value = json!({"name": "rg", "location": "eastus", "id": "/x", "tags": {"a": "b"}});
+        assert_eq!(
+            render(Some(&value), OutputFormat::Table, Some(&schema())).unwrap(),
+            "Id  Location  Name\n--  --------  ----\n/x  eastus    rg"
+        );
+    }
+
+    #[test]
+    fn render_tsv_list() {
+        let value = json!({"value": [{"name": "a", "location": "eastus"}, {"name": "b", "location": "westus"}], "nextLink": "x"});
+        assert_eq!(
+            render(Some(&value), OutputFormat::Tsv, None).unwrap(),
+            "eastus\ta\nwestus\tb"
+        );
+    }
+
+    #[test]
+    fn render_table_scalars() {
+        let value = json!(["a", "b"]);
+        assert_eq!(
+            render(Some(&value), OutputFormat::Table, None).unwrap(),
+            "Result\n------\na\nb"
+        );
+    }
+
+    #[test]
+    fn query_result() {
+        let value = json!({"value": [{"id": "a", "properties": {"provisioningState": "Succeeded"}}, {"id": "b"}]});
+        assert_eq!(
+            query(Some(&value), "value[].id").unwrap(),
+            Some(json!(["a", "b"]))
+        );
+        assert_eq!(
+            query(Some(&value), "value[0].properties.provisioningState").unwrap(),
+            Some(json!("Succeeded"))
+        );
+        assert_eq!(query(Some(&value), "foo").unwrap(), None);
+        assert!(parse_query("value[").is_err());
+    }
+
+    #[test]
+    fn render_yaml() {
+        let value = json!({"name": "rg", "tags": {"a": "b"}});
+        assert_eq!(
+            render(Some(&value), OutputFormat::Yaml, None).unwrap(),
+            "name: rg\ntags:\n  a: b"
+        );
+    }
+}
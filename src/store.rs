@@ -0,0 +1,262 @@
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::client::PollerHandle;
+
+const JOBS: &str = "jobs";
+const ACCOUNT: &str = "account";
+
+/// Job is an outstanding long running operation, which is started with `--no-wait`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    // The command line that started the operation.
+    pub command: String,
+    // The last known status of the operation.
+    pub status: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    pub handle: PollerHandle,
+}
+
+/// Account is the subscription that the commands run against by default, which is set by `account set`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Account {
+    // The subscription id.
+    pub id: String,
+    // The display name of the subscription.
+    pub name: String,
+    #[serde(rename = "tenantId")]
+    pub tenant_id: String,
+}
+
+/// Store persists the local state of the CLI as named JSON documents.
+///
+/// The documents are stored as files in the directory specified by `AZURE_RS_CONFIG_DIR`, which defaults to
+/// `~/.azure-rs`. In the browser, they are kept in memory for the lifetime of the page.
+pub struct Store {
+    #[cfg(not(target_arch = "wasm32"))]
+    dir: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Store {
+    pub fn open() -> Result<Self> {
+        let dir = match std::env::var_os("AZURE_RS_CONFIG_DIR") {
+            Some(dir) => std::path::PathBuf::from(dir),
+            None => std::env::home_dir()
+                .ok_or(anyhow!(
+                    "can't locate the home directory, set AZURE_RS_CONFIG_DIR instead"
+                ))?
+                .join(".azure-rs"),
+        };
+        Ok(Self::new(dir))
+    }
+
+    pub fn new(dir: std::path::PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn read(&self, name: &str) -> Result<Option<String>> {
+        match std::fs::read_to_string(self.dir.join(format!("{name}.json"))) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // write writes the document to a temporary file and renames it over, so that the readers never see it partially
+    // written.
+    fn write(&self, name: &str, content: String) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let temp = self
+            .dir
+            .join(format!("{name}.json.{}.tmp", uuid::Uuid::new_v4()));
+        std::fs::write(&temp, content)?;
+        std::fs::rename(&temp, self.dir.join(format!("{name}.json"))).inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp);
+        })?;
+        Ok(())
+    }
+
+    // lock takes the advisory lock of the document, which is released once the returned file is dropped. It
+    // serializes the read-modify-write of the document across the processes, e.g. the concurrent "--no-wait" commands.
+    fn lock(&self, name: &str) -> Result<std::fs::File> {
+        std::fs::create_dir_all(&self.dir)?;
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.dir.join(format!("{name}.lock")))?;
+        file.lock()?;
+        Ok(file)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+static DOCUMENTS: std::sync::Mutex<std::collections::BTreeMap<String, String>> =
+    std::sync::Mutex::new(std::collections::BTreeMap::new());
+
+#[cfg(target_arch = "wasm32")]
+struct NoLock;
+
+#[cfg(target_arch = "wasm32")]
+impl Store {
+    pub fn open() -> Result<Self> {
+        Ok(Self {})
+    }
+
+    fn read(&self, name: &str) -> Result<Option<String>> {
+        let docs = DOCUMENTS.lock().map_err(|e| anyhow!("{e}"))?;
+        Ok(docs.get(name).cloned())
+    }
+
+    fn write(&self, name: &str, content: String) -> Result<()> {
+        let mut docs = DOCUMENTS.lock().map_err(|e| anyhow!("{e}"))?;
+        docs.insert(name.to_string(), content);
+        Ok(())
+    }
+
+    // There is a single page that accesses the documents, no lock is needed.
+    fn lock(&self, _name: &str) -> Result<NoLock> {
+        Ok(NoLock)
+    }
+}
+
+impl Store {
+    pub fn load<T: DeserializeOwned + Default>(&self, name: &str) -> Result<T> {
+        match self.read(name)? {
+            Some(content) => serde_json::from_str(&content)
+                .map_err(|e| anyhow!(r#"reading "{name}" from the local store: {e}"#)),
+            None => Ok(T::default()),
+        }
+    }
+
+    pub fn save<T: Serialize>(&self, name: &str, value: &T) -> Result<()> {
+        self.write(name, serde_json::to_string_pretty(value)?)
+    }
+
+    // update modifies the document in place, which is locked against the other processes in the meantime.
+    pub fn update<T: Serialize + DeserializeOwned + Default>(
+        &self,
+        name: &str,
+        modify: impl FnOnce(&mut T),
+    ) -> Result<()> {
+        let _lock = self.lock(name)?;
+        let mut value = self.load(name)?;
+        modify(&mut value);
+        self.save(name, &value)
+    }
+
+    pub fn jobs(&self) -> Result<Vec<Job>> {
+        self.load(JOBS)
+    }
+
+    pub fn job(&self, id: &str) -> Result<Job> {
+        self.jobs()?
+            .into_iter()
+            .find(|job| job.id == id)
+            .ok_or(anyhow!(r#"operation "{id}" not found"#))
+    }
+
+    // put_job adds the job, or replaces the one with the same id.
+    pub fn put_job(&self, job: Job) -> Result<()> {
+        self.update(JOBS, |jobs: &mut Vec<Job>| {
+            match jobs.iter_mut().find(|j| j.id == job.id) {
+                Some(j) => *j = job,
+                None => jobs.push(job),
+            }
+        })
+    }
+
+    pub fn remove_job(&self, id: &str) -> Result<()> {
+        self.update(JOBS, |jobs: &mut Vec<Job>| jobs.retain(|job| job.id != id))
+    }
+
+    pub fn account(&self) -> Result<Option<Account>> {
+        self.load(ACCOUNT)
+    }
+
+    pub fn set_account(&self, account: &Account) -> Result<()> {
+        self.save(ACCOUNT, account)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+    use super::*;
+    use crate::client::poller::PollerKind;
+    use pretty_assertions::assert_eq;
+
+    fn job(id: &str, status: &str) -> Job {
+        Job {
+            id: id.to_string(),
+            command: "azure api resource group delete -g foo".to_string(),
+            status: status.to_string(),
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            handle: PollerHandle {
+                kind: PollerKind::AsyncOp,
+                polling_url: Some("https://management.azure.com/operations/1".to_string()),
+                location_url: None,
+                original_url: "https://management.azure.com/foo".to_string(),
+                method: "DELETE".to_string(),
+                final_state_via: None,
+                result_path: None,
+            },
+        }
+    }
+
+    #[test]
+    fn jobs() {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let store = Store::new(dir.clone());
+        assert_eq!(store.jobs().unwrap(), vec![]);
+
+        store.put_job(job("a", "InProgress")).unwrap();
+        store.put_job(job("b", "InProgress")).unwrap();
+        store.put_job(job("a", "Succeeded")).unwrap();
+        assert_eq!(
+            Store::new(dir.clone()).jobs().unwrap(),
+            vec![job("a", "Succeeded"), job("b", "InProgress")]
+        );
+
+        store.remove_job("a").unwrap();
+        assert!(store.job("a").is_err());
+        assert_eq!(store.job("b").unwrap(), job("b", "InProgress"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_jobs() {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    Store::new(dir)
+                        .put_job(job(&i.to_string(), "InProgress"))
+                        .unwrap()
+                })
+            })
+            .collect();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        assert_eq!(Store::new(dir.clone()).jobs().unwrap().len(), 8);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn account() {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let store = Store::new(dir.clone());
+        assert_eq!(store.account().unwrap(), None);
+
+        let account = Account {
+            id: "00000000-0000-0000-0000-000000000000".to_string(),
+            name: "foo".to_string(),
+            tenant_id: "11111111-1111-1111-1111-111111111111".to_string(),
+        };
+        store.set_account(&account).unwrap();
+        assert_eq!(Store::new(dir.clone()).account().unwrap(), Some(account));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,6 +1,8 @@
 use crate::azidentityext::access_token_credential::AccessTokenCredential;
-use crate::client::Client;
+use crate::client::{CancellationToken, Client, PollProgress, ProgressHandler};
+use crate::error::{ArmError, ConfirmationRequired, Interrupted};
 use crate::run;
+use azure_core::http::Context;
 use std::fmt::Debug;
 use std::{path::PathBuf, result::Result};
 use wasm_bindgen::prelude::*;
@@ -8,21 +10,130 @@ use wasm_bindgen::prelude::*;
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
+    pub(crate) fn log(s: &str);
+
+    #[wasm_bindgen(js_namespace = console)]
+    pub(crate) fn warn(s: &str);
+}
+
+// The progress callback is only called on the main thread, as WASM is single threaded.
+struct ProgressCallback(js_sys::Function);
+
+unsafe impl Send for ProgressCallback {}
+unsafe impl Sync for ProgressCallback {}
+
+impl ProgressCallback {
+    fn call(&self, progress: &PollProgress) {
+        let progress = serde_json::json!({
+            "status": progress.status,
+            "percentComplete": progress.percent_complete,
+            "startTime": progress.start_time,
+            "nextDelaySeconds": progress.next_delay.map(|d| d.as_secs_f64()),
+        });
+        if let Ok(progress) = js_sys::JSON::parse(&progress.to_string()) {
+            // The errors thrown by the callback are ignored, which shall not interrupt the polling
+            let _ = self.0.call1(&JsValue::NULL, &progress);
+        }
+    }
+}
+
+// AbortListener cancels the command once the AbortSignal is aborted, until it is dropped.
+struct AbortListener {
+    signal: JsValue,
+    listener: Closure<dyn FnMut()>,
+}
+
+impl AbortListener {
+    fn new(signal: JsValue, token: CancellationToken) -> Result<Self, JsValue> {
+        if js_sys::Reflect::get(&signal, &"aborted".into())?.is_truthy() {
+            token.cancel();
+        }
+        let listener = Closure::<dyn FnMut()>::new(move || token.cancel());
+        Self::call(&signal, "addEventListener", &listener)?;
+        Ok(Self { signal, listener })
+    }
+
+    fn call(
+        signal: &JsValue,
+        method: &str,
+        listener: &Closure<dyn FnMut()>,
+    ) -> Result<JsValue, JsValue> {
+        let method: js_sys::Function = js_sys::Reflect::get(signal, &method.into())?.dyn_into()?;
+        method.call2(signal, &"abort".into(), listener.as_ref())
+    }
+}
+
+impl Drop for AbortListener {
+    fn drop(&mut self) {
+        let _ = Self::call(&self.signal, "removeEventListener", &self.listener);
+    }
 }
 
+/// Runs the CLI with the arguments.
+///
+/// The optional `progress` callback is called after each poll of a long running operation, with an object
+/// of `{status, percentComplete, startTime, nextDelaySeconds}`.
+///
+/// The optional `signal` is an `AbortSignal`, which cancels the command once aborted. The polling of the
+/// long running operation stops, and its last known state is reported in the error.
 #[wasm_bindgen]
-pub async fn run_cli(args: Vec<String>, token: &str) -> Result<String, JsValue> {
+pub async fn run_cli(
+    args: Vec<String>,
+    token: &str,
+    progress: Option<js_sys::Function>,
+    signal: Option<js_sys::Object>,
+) -> Result<String, JsValue> {
     console_error_panic_hook::set_once();
     let credential = AccessTokenCredential::new(token.to_string()).map_err(jsfy)?;
-    let client = Client::new(
+    let mut client = Client::new(
         "https://management.azure.com",
         vec!["https://management.azure.com/.default"],
         credential,
         None,
     )
     .map_err(jsfy)?;
-    run(PathBuf::new(), &client, args).await.map_err(jsfy)
+    if let Some(progress) = progress {
+        let callback = ProgressCallback(progress);
+        client = client.with_progress(ProgressHandler::new(move |p| callback.call(p)));
+    }
+    let cancellation = CancellationToken::new();
+    let _listener = signal
+        .map(|signal| AbortListener::new(signal.into(), cancellation.clone()))
+        .transpose()?;
+    let ctx = Context::new().with_value(cancellation);
+    run(PathBuf::new(), &client, args, &ctx).await.map_err(|e| {
+        if let Some(e) = e.downcast_ref::<ConfirmationRequired>() {
+            // Let the caller confirm with the user, then rerun the command with "--yes"
+            return JsValue::from_str(
+                &serde_json::json!({
+                    "confirmationRequired": true,
+                    "message": e.message,
+                })
+                .to_string(),
+            );
+        }
+        if let Some(interrupted) = e.downcast_ref::<Interrupted>() {
+            return JsValue::from_str(
+                &serde_json::json!({
+                    "interrupted": true,
+                    "message": interrupted.to_string(),
+                    "status": interrupted.status,
+                    "id": interrupted.id,
+                })
+                .to_string(),
+            );
+        }
+        if let Some(arm_error) = e.downcast_ref::<ArmError>() {
+            return JsValue::from_str(
+                &serde_json::json!({
+                    "armError": arm_error,
+                    "message": arm_error.to_string(),
+                })
+                .to_string(),
+            );
+        }
+        jsfy(e)
+    })
 }
 
 fn jsfy<E>(e: E) -> JsValue
@@ -1,6 +1,6 @@
-import React, { useState, useEffect } from 'react';
+import React, { useState, useEffect, useRef } from 'react';
 import { useAzureAuth } from '../hooks/useAzureAuth';
-import { loadWasmModule, type WasmModule } from '../utils/wasmLoader';
+import { loadWasmModule, type ArmErrorResponse, type ConfirmationRequired, type Interrupted, type PollProgress, type WasmModule } from '../utils/wasmLoader';
 
 export const AzureCLIInterface: React.FC = () => {
   const [command, setCommand] = useState('');
@@ -9,6 +9,7 @@ export const AzureCLIInterface: React.FC = () => {
   const [wasmReady, setWasmReady] = useState(false);
   const [initializingWasm, setInitializingWasm] = useState(false);
   const [wasmModule, setWasmModule] = useState<WasmModule | null>(null);
+  const abortController = useRef<AbortController | null>(null);
   
   const { account, getAzureManagementToken } = useAzureAuth();
 
@@ -78,6 +79,42 @@ export const AzureCLIInterface: React.FC = () => {
     return args;
   };
 
+  const parseConfirmationRequired = (error: unknown): ConfirmationRequired | null => {
+    if (typeof error !== 'string') {
+      return null;
+    }
+    try {
+      const parsed = JSON.parse(error);
+      return parsed?.confirmationRequired ? parsed : null;
+    } catch {
+      return null;
+    }
+  };
+
+  const parseInterrupted = (error: unknown): Interrupted | null => {
+    if (typeof error !== 'string') {
+      return null;
+    }
+    try {
+      const parsed = JSON.parse(error);
+      return parsed?.interrupted ? parsed : null;
+    } catch {
+      return null;
+    }
+  };
+
+  const parseArmError = (error: unknown): ArmErrorResponse | null => {
+    if (typeof error !== 'string') {
+      return null;
+    }
+    try {
+      const parsed = JSON.parse(error);
+      return parsed?.armError ? parsed : null;
+    } catch {
+      return null;
+    }
+  };
+
   const executeCommand = async () => {
     if (!wasmReady || !wasmModule || !account) {
       setOutput('WASM module not ready or user not authenticated');
@@ -91,6 +128,8 @@ export const AzureCLIInterface: React.FC = () => {
 
     setLoading(true);
     setOutput('Executing Azure CLI command...');
+    const controller = new AbortController();
+    abortController.current = controller;
 
     try {
       // Get access token for Azure Management API
@@ -106,11 +145,31 @@ export const AzureCLIInterface: React.FC = () => {
       // Note: For MSAL authentication, we don't have client_id and secret in the traditional sense
       // The WASM module might need to be updated to handle access tokens instead
       // For now, we'll pass the access token as the secret parameter
-      const result = await wasmModule.run_cli(
-        args,
+      const onProgress = (progress: PollProgress) => {
+        const percent = progress.percentComplete != null ? `, ${progress.percentComplete}%` : '';
+        setOutput(`Waiting for the long running operation to finish (${progress.status}${percent})...`);
+      };
+      let result: string;
+      try {
+        result = await wasmModule.run_cli(
+          args,
 
-        accessToken // Using access token instead of client secret
-      );
+          accessToken, // Using access token instead of client secret
+          onProgress,
+          controller.signal
+        );
+      } catch (error) {
+        // Destructive commands need to be confirmed, rerun it with "--yes" once the user confirms
+        const confirmation = parseConfirmationRequired(error);
+        if (!confirmation) {
+          throw error;
+        }
+        if (!window.confirm(confirmation.message)) {
+          setOutput('Operation cancelled.');
+          return;
+        }
+        result = await wasmModule.run_cli([...args, '--yes'], accessToken, onProgress, controller.signal);
+      }
 
       // Try to format JSON if the result is valid JSON
       let formattedResult = result;
@@ -124,6 +183,16 @@ export const AzureCLIInterface: React.FC = () => {
 
       setOutput(formattedResult);
     } catch (error) {
+      const interrupted = parseInterrupted(error);
+      if (interrupted) {
+        setOutput(interrupted.message);
+        return;
+      }
+      const armError = parseArmError(error);
+      if (armError) {
+        setOutput(`Error: ${armError.message}`);
+        return;
+      }
       console.error('CLI execution failed:', error);
       let errorMessage = 'CLI execution failed';
       
@@ -141,10 +210,15 @@ export const AzureCLIInterface: React.FC = () => {
       
       setOutput(`Error: ${errorMessage}`);
     } finally {
+      abortController.current = null;
       setLoading(false);
     }
   };
 
+  const cancelCommand = () => {
+    abortController.current?.abort();
+  };
+
   const clearOutput = () => {
     setOutput('Output cleared. Ready for next command.');
   };
@@ -196,6 +270,14 @@ export const AzureCLIInterface: React.FC = () => {
         >
           {loading ? 'Executing...' : 'Execute Command'}
         </button>
+        {loading && (
+          <button
+            onClick={cancelCommand}
+            className="cancel-btn"
+          >
+            Cancel
+          </button>
+        )}
         <button
           onClick={clearOutput}
           className="clear-btn"
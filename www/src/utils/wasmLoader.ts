@@ -1,6 +1,54 @@
 // WASM module types
 export interface WasmModule {
-  run_cli: (args: string[],  token: string) => Promise<string>;
+  run_cli: (
+    args: string[],
+    token: string,
+    progress?: (progress: PollProgress) => void,
+    signal?: AbortSignal,
+  ) => Promise<string>;
+}
+
+// The progress of a long running operation, reported after each poll
+export interface PollProgress {
+  status: string;
+  percentComplete: number | null;
+  startTime: string | null;
+  nextDelaySeconds: number | null;
+}
+
+// The error thrown by run_cli (as a JSON string) when the command needs to be confirmed by the user
+export interface ConfirmationRequired {
+  confirmationRequired: true;
+  message: string;
+}
+
+// The error thrown by run_cli (as a JSON string) when the command is cancelled via the AbortSignal or timed out
+export interface Interrupted {
+  interrupted: true;
+  message: string;
+  // The last known status of the long running operation, if any
+  status: string | null;
+  // The id to wait for the long running operation later, i.e. "operation wait --id <id>"
+  id: string | null;
+}
+
+// The error detail in the ARM error format
+export interface ErrorDetail {
+  code: string;
+  message: string;
+  target?: string;
+  details?: ErrorDetail[];
+  additionalInfo?: { type: string; info: unknown }[];
+}
+
+// The error thrown by run_cli (as a JSON string) when ARM responds with an error, or the long running operation fails
+export interface ArmErrorResponse {
+  armError: {
+    status: number;
+    error: ErrorDetail;
+  };
+  // The readable rendering of the error
+  message: string;
 }
 
 // Simple cache to avoid re-initializing